@@ -0,0 +1,2 @@
+ALTER TABLE solution
+    DROP COLUMN seq
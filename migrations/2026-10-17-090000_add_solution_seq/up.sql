@@ -0,0 +1,2 @@
+ALTER TABLE solution
+    ADD COLUMN seq BIGINT NOT NULL DEFAULT 0
@@ -0,0 +1,12 @@
+CREATE TABLE chat_message
+(
+    id            BIGSERIAL NOT NULL PRIMARY KEY,
+    crossword_for VARCHAR   NOT NULL,
+    team_for      VARCHAR   NOT NULL,
+    user_id       VARCHAR   NOT NULL,
+    name          VARCHAR   NOT NULL,
+    text          TEXT      NOT NULL,
+    sent_at       BIGINT    NOT NULL
+);
+
+CREATE INDEX chat_message_room_idx ON chat_message (crossword_for, team_for, id);
@@ -0,0 +1 @@
+DROP TABLE clue_comment;
@@ -0,0 +1,14 @@
+CREATE TABLE clue_comment
+(
+    id            BIGSERIAL NOT NULL PRIMARY KEY,
+    crossword_for VARCHAR   NOT NULL,
+    team_for      VARCHAR   NOT NULL,
+    clue_number   BIGINT    NOT NULL,
+    direction     VARCHAR   NOT NULL,
+    user_id       VARCHAR   NOT NULL,
+    name          VARCHAR   NOT NULL,
+    text          TEXT      NOT NULL,
+    created_at    BIGINT    NOT NULL
+);
+
+CREATE INDEX clue_comment_room_idx ON clue_comment (crossword_for, team_for, clue_number, direction);
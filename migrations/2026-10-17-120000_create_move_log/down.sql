@@ -0,0 +1 @@
+DROP TABLE move_log;
@@ -0,0 +1,13 @@
+CREATE TABLE move_log
+(
+    id            BIGSERIAL NOT NULL PRIMARY KEY,
+    crossword_for VARCHAR   NOT NULL,
+    team_for      VARCHAR   NOT NULL,
+    seq           BIGINT    NOT NULL,
+    user_id       VARCHAR   NOT NULL,
+    cells_before  jsonb     NOT NULL,
+    cells_after   jsonb     NOT NULL,
+    created_at    BIGINT    NOT NULL
+);
+
+CREATE INDEX move_log_room_idx ON move_log (crossword_for, team_for, seq);
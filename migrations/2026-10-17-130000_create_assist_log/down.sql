@@ -0,0 +1 @@
+DROP TABLE assist_log;
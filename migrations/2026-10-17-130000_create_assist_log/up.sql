@@ -0,0 +1,12 @@
+CREATE TABLE assist_log
+(
+    id            BIGSERIAL NOT NULL PRIMARY KEY,
+    crossword_for VARCHAR   NOT NULL,
+    team_for      VARCHAR   NOT NULL,
+    user_id       VARCHAR   NOT NULL,
+    action        VARCHAR   NOT NULL,
+    target        jsonb     NOT NULL,
+    created_at    BIGINT    NOT NULL
+);
+
+CREATE INDEX assist_log_room_idx ON assist_log (crossword_for, team_for);
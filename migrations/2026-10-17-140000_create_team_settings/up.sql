@@ -0,0 +1,5 @@
+CREATE TABLE team_settings
+(
+    team_id       VARCHAR NOT NULL PRIMARY KEY,
+    reveal_policy VARCHAR NOT NULL DEFAULT 'auto'
+);
@@ -0,0 +1,12 @@
+CREATE TABLE completion
+(
+    crossword_for VARCHAR NOT NULL,
+    team_for      VARCHAR NOT NULL,
+    started_at    BIGINT,
+    completed_at  BIGINT  NOT NULL,
+    assists       BIGINT  NOT NULL,
+    contributors  TEXT[]  NOT NULL,
+    PRIMARY KEY (crossword_for, team_for)
+);
+
+CREATE INDEX completion_team_idx ON completion (team_for);
@@ -0,0 +1 @@
+DROP TABLE solve_timer;
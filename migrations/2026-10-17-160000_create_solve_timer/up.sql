@@ -0,0 +1,10 @@
+CREATE TABLE solve_timer
+(
+    crossword_for VARCHAR NOT NULL,
+    team_for      VARCHAR NOT NULL,
+    started_at    BIGINT,
+    elapsed       BIGINT  NOT NULL DEFAULT 0,
+    running_since BIGINT,
+    paused        BOOLEAN NOT NULL DEFAULT FALSE,
+    PRIMARY KEY (crossword_for, team_for)
+);
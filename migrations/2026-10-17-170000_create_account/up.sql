@@ -0,0 +1,7 @@
+CREATE TABLE account
+(
+    username      VARCHAR NOT NULL PRIMARY KEY,
+    display_name  VARCHAR NOT NULL,
+    password_hash VARCHAR NOT NULL,
+    created_at    BIGINT  NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP TABLE team_membership;
+DROP TABLE team;
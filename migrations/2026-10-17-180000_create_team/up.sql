@@ -0,0 +1,18 @@
+CREATE TABLE team
+(
+    id          VARCHAR NOT NULL PRIMARY KEY,
+    name        VARCHAR NOT NULL,
+    invite_code VARCHAR NOT NULL UNIQUE,
+    created_at  BIGINT  NOT NULL
+);
+
+CREATE TABLE team_membership
+(
+    team_id   VARCHAR NOT NULL REFERENCES team (id) ON DELETE CASCADE,
+    username  VARCHAR NOT NULL REFERENCES account (username),
+    role      VARCHAR NOT NULL,
+    joined_at BIGINT  NOT NULL,
+    PRIMARY KEY (team_id, username)
+);
+
+CREATE INDEX team_membership_username_idx ON team_membership (username);
@@ -0,0 +1,10 @@
+CREATE TABLE ingestion_audit
+(
+    id           BIGSERIAL NOT NULL PRIMARY KEY,
+    triggered_by VARCHAR   NOT NULL,
+    action       VARCHAR   NOT NULL,
+    series       VARCHAR,
+    start_id     BIGINT,
+    end_id       BIGINT,
+    created_at   BIGINT    NOT NULL
+);
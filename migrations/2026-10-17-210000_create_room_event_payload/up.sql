@@ -0,0 +1,6 @@
+CREATE TABLE room_event_payload
+(
+    id         BIGSERIAL NOT NULL PRIMARY KEY,
+    payload    TEXT      NOT NULL,
+    created_at BIGINT    NOT NULL
+);
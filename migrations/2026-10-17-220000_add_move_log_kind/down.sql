@@ -0,0 +1,5 @@
+DROP INDEX move_log_user_idx;
+
+ALTER TABLE move_log
+    DROP COLUMN kind,
+    DROP COLUMN reverts;
@@ -0,0 +1,5 @@
+ALTER TABLE move_log
+    ADD COLUMN kind    VARCHAR NOT NULL DEFAULT 'move',
+    ADD COLUMN reverts BIGINT;
+
+CREATE INDEX move_log_user_idx ON move_log (crossword_for, team_for, user_id, seq);
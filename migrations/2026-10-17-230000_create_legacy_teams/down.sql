@@ -0,0 +1,5 @@
+-- Teams created for legacy solves are named after their id, and only removed while still unclaimed
+DELETE
+FROM team
+WHERE name = id
+  AND NOT EXISTS (SELECT 1 FROM team_membership WHERE team_membership.team_id = team.id);
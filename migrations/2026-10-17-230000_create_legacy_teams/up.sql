@@ -0,0 +1,16 @@
+-- Solves from before teams had members were keyed by whatever team name was typed in. Give each such team a row,
+-- named after it and with no members, so its solves can be reached again by joining it with its invite code.
+INSERT INTO team (id, name, invite_code, created_at)
+SELECT legacy.team_for,
+       legacy.team_for,
+       md5(random()::text || clock_timestamp()::text || legacy.team_for),
+       (extract(epoch FROM now()) * 1000)::BIGINT
+FROM (SELECT team_for FROM solution
+      UNION SELECT team_for FROM chat_message
+      UNION SELECT team_for FROM clue_comment
+      UNION SELECT team_for FROM move_log
+      UNION SELECT team_for FROM assist_log
+      UNION SELECT team_for FROM completion
+      UNION SELECT team_for FROM solve_timer
+      UNION SELECT team_id FROM team_settings) AS legacy
+WHERE NOT EXISTS (SELECT 1 FROM team WHERE team.id = legacy.team_for);
@@ -0,0 +1,4 @@
+DROP INDEX solution_team_activity_idx;
+
+ALTER TABLE solution
+    DROP COLUMN last_activity;
@@ -0,0 +1,10 @@
+-- When a cell of the solution was last written, in milliseconds, or 0 if none have been written since cells were
+-- timestamped, so a team's progress can be listed a page at a time, most recently worked on first.
+ALTER TABLE solution
+    ADD COLUMN last_activity BIGINT NOT NULL DEFAULT 0;
+
+UPDATE solution
+SET last_activity = COALESCE((SELECT max((item ->> 'updated_at')::BIGINT)
+                              FROM jsonb_array_elements(solution_json) AS item), 0);
+
+CREATE INDEX solution_team_activity_idx ON solution (team_for, last_activity, crossword_for);
@@ -1,21 +1,40 @@
 use crate::models::errors::{to_status_code, AppError};
 use actix::{Actor, Addr};
 use actix_cors::Cors;
-use actix_web::web::{self, Data, Path, Payload};
+use actix_web::dev::ServiceRequest;
+use actix_web::web::{self, Data, Path, Payload, Query};
 use actix_web::{
-    get, middleware, post, App, Error, HttpRequest, HttpResponse, HttpServer, Responder,
+    delete, get, middleware, post, put, App, Error, HttpRequest, HttpResponse, HttpServer,
+    Responder,
 };
 use actix_web_actors::ws::start;
 use diesel::r2d2;
 use diesel::PgConnection;
-use models::api_models::{CrosswordMetadata, CrosswordMetadataWithHumanDate};
+use models::api_models::{
+    AccountDto, CrosswordQuery, Direction, LoginDto, NewTeamDto, ProgressQuery, RegistrationDto,
+    RoleDto, TeamRole, TeamSettingsDto,
+};
 use serde::{Deserialize, Serialize};
-use services::util::to_human_readable_date;
+use std::time::Duration;
+use uuid::Uuid;
 
-use crate::services::crossword_db_actions::{
-    get_crossword_for_series_and_number, get_crossword_metadata_for_series,
+use crate::services::auth_service::{
+    login, register, Admin, AdminApiKey, AuthenticatedUser, TokenKeys,
+};
+use crate::services::comment_service::retrieve_clue_comments;
+use crate::services::completion_service::retrieve_completions;
+use crate::services::crossword_service::{
+    get_crossword_dto, list_crosswords, record_ingestion, validate_bulk_range,
+};
+use crate::services::fanout;
+use crate::services::fanout::Fanout;
+use crate::services::solution_service::{retrieve_progress, retrieve_replay, ReplayCompression};
+use crate::services::solution_writer::SolutionWriter;
+use crate::services::team_service::{
+    change_role, create_team, join_team, remove_member, require_role, reset_invite_code,
+    retrieve_team, retrieve_team_settings, retrieve_teams, update_team_settings,
 };
-use crate::services::ws_server::MoveServer;
+use crate::services::ws_server::{MemberRemoved, MoveServer};
 use crate::services::ws_session::WsSession;
 
 mod models;
@@ -24,6 +43,9 @@ mod services;
 
 type DbPool = r2d2::Pool<r2d2::ConnectionManager<PgConnection>>;
 
+/// actix-web's default access log format, with the request line swapped for one without session tokens.
+const ACCESS_LOG_FORMAT: &str = r#"%a "%{request}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#;
+
 static ALL_SERIES: [&str; 6] = ["quiptic", "quick", "cryptic", "speedy", "prize", "everyman"];
 
 #[actix_web::main]
@@ -32,8 +54,18 @@ async fn main() -> std::io::Result<()> {
     env_logger::init();
     dotenv::dotenv().ok();
     let pool = initialize_db_pool();
-    let server = MoveServer::new(pool.clone()).start();
-    
+    let instance = Uuid::new_v4();
+    let writer = SolutionWriter::new(pool.clone()).start();
+    let fanout = Fanout::new(instance, pool.clone()).start();
+    let server = MoveServer::new(pool.clone(), writer, fanout, cursor_idle_after()).start();
+    let token_keys = Data::new(TokenKeys::new(token_secret().as_bytes()));
+    let admin_api_key = Data::new(AdminApiKey(
+        std::env::var("ADMIN_API_KEY")
+            .ok()
+            .filter(|key| !key.is_empty()),
+    ));
+    fanout::listen(database_url(), instance, server.clone());
+
     // Update all crosswords on startup
     let pool_clone = pool.clone();
     tokio::spawn(async move {
@@ -44,18 +76,39 @@ async fn main() -> std::io::Result<()> {
             println!("Some errors occurred while updating crosswords on startup");
         }
     });
-    
+
     HttpServer::new(move || {
         App::new()
             .wrap(Cors::default().allow_any_method().allow_any_origin())
-            .wrap(middleware::Logger::default())
+            .wrap(
+                middleware::Logger::new(ACCESS_LOG_FORMAT)
+                    .custom_request_replace("request", redacted_request_line),
+            )
             .app_data(Data::new(pool.clone()))
             .app_data(Data::new(server.clone()))
+            .app_data(token_keys.clone())
+            .app_data(admin_api_key.clone())
             .service(get_crossword_data)
             .service(get_all_crossword_data)
             .service(update_crosswords)
             .service(bulk_update_crosswords)
             .service(update_all_crosswords)
+            .service(get_comments)
+            .service(get_replay)
+            .service(get_completions)
+            .service(post_team)
+            .service(get_teams)
+            .service(get_team_settings)
+            .service(get_progress)
+            .service(put_team_settings)
+            .service(get_team)
+            .service(post_invite_code)
+            .service(accept_invite)
+            .service(put_member_role)
+            .service(delete_member)
+            .service(create_account)
+            .service(create_session)
+            .service(get_current_user)
             .service(start_connection)
     })
     .bind(std::env::var("HOST_PORT").unwrap_or("127.0.0.1:8080".to_string()))?
@@ -70,9 +123,39 @@ struct PostData {
     end_id: i64,
 }
 
+/// Guards against scraping pages for series that don't exist.
+fn validate_series(series: &str) -> Result<(), AppError> {
+    if ALL_SERIES.contains(&series) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!("Unknown series: {}", series)))
+    }
+}
+
 #[post("/bulk-update-crosswords")]
-async fn bulk_update_crosswords(pool: Data<DbPool>, data: web::Json<PostData>) -> impl Responder {
-    println!("Bulk update request: {:#?}", data);
+async fn bulk_update_crosswords(
+    pool: Data<DbPool>,
+    data: web::Json<PostData>,
+    admin: Admin,
+) -> impl Responder {
+    println!("Bulk update request from {}: {:#?}", admin.actor, data);
+    // Only runs that go ahead are recorded
+    if let Err(error) =
+        validate_series(&data.series).and_then(|_| validate_bulk_range(data.start_id, data.end_id))
+    {
+        return build_error_response(error);
+    }
+    if let Err(error) = record_ingestion(
+        pool.clone(),
+        admin.actor,
+        "bulk-update",
+        Some(data.series.clone()),
+        Some((data.start_id, data.end_id)),
+    )
+    .await
+    {
+        return build_error_response(error);
+    }
 
     let result = services::crossword_service::bulk_update_series(
         pool,
@@ -106,7 +189,11 @@ async fn update_all_crosswords_internal(pool: Data<DbPool>) -> bool {
 }
 
 #[post("/update-all-crosswords")]
-async fn update_all_crosswords(pool: Data<DbPool>) -> impl Responder {
+async fn update_all_crosswords(pool: Data<DbPool>, admin: Admin) -> impl Responder {
+    if let Err(error) = record_ingestion(pool.clone(), admin.actor, "update-all", None, None).await
+    {
+        return build_error_response(error);
+    }
     let success = update_all_crosswords_internal(pool).await;
     let message = if success {
         "Successfully updated all crosswords"
@@ -117,8 +204,26 @@ async fn update_all_crosswords(pool: Data<DbPool>) -> impl Responder {
 }
 
 #[post("/update-crosswords/{series}")]
-async fn update_crosswords(pool: Data<DbPool>, path: Path<(String,)>) -> impl Responder {
+async fn update_crosswords(
+    pool: Data<DbPool>,
+    path: Path<(String,)>,
+    admin: Admin,
+) -> impl Responder {
     let series = path.into_inner().0;
+    if let Err(error) = validate_series(&series) {
+        return build_error_response(error);
+    }
+    if let Err(error) = record_ingestion(
+        pool.clone(),
+        admin.actor,
+        "update",
+        Some(series.clone()),
+        None,
+    )
+    .await
+    {
+        return build_error_response(error);
+    }
     let page = 1;
     let result = services::crossword_service::update_crosswords(pool, &series, &page).await;
     match result {
@@ -127,12 +232,22 @@ async fn update_crosswords(pool: Data<DbPool>, path: Path<(String,)>) -> impl Re
     }
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CrosswordParams {
+    spoiler_free: Option<bool>,
+}
+
 #[get("/crossword/{series}/{seriesNo}")]
-async fn get_crossword_data(pool: Data<DbPool>, path: Path<(String, String)>) -> impl Responder {
+async fn get_crossword_data(
+    pool: Data<DbPool>,
+    path: Path<(String, String)>,
+    query: Query<CrosswordParams>,
+) -> impl Responder {
     let params = path.into_inner();
     let series = params.0;
     let series_no = params.1;
-    let crossword_data = get_crossword_for_series_and_number(pool, series_no, series).await;
+    let crossword_data = get_crossword_dto(pool, series, series_no, query.spoiler_free).await;
     match crossword_data {
         Ok(message) => serde_json::to_string(&message).map_or(
             HttpResponse::BadRequest().body("Couldn't parse crossword to a string"),
@@ -143,61 +258,434 @@ async fn get_crossword_data(pool: Data<DbPool>, path: Path<(String, String)>) ->
 }
 
 #[get("/crosswords")]
-async fn get_all_crossword_data(pool: Data<DbPool>) -> impl Responder {
-    let mut all_crosswords: Vec<CrosswordMetadata> = Vec::new();
+async fn get_all_crossword_data(
+    pool: Data<DbPool>,
+    query: Query<CrosswordQuery>,
+) -> impl Responder {
+    match list_crosswords(pool, query.into_inner(), &ALL_SERIES).await {
+        Ok(page) => serde_json::to_string(&page).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse crossword to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
 
-    for series in ALL_SERIES.iter() {
-        let crossword_result =
-            get_crossword_metadata_for_series(pool.clone(), series.to_string()).await;
-        match crossword_result {
-            Ok(crosswords) => {
-                all_crosswords.extend(crosswords);
-            }
-            Err(error) => {
-                println!("Error: {:#?}", error);
-            }
+#[derive(Deserialize, Debug)]
+struct CommentParams {
+    clue: Option<i64>,
+    direction: Option<Direction>,
+}
+
+#[get("/comments/{team_id}/{crossword_id}")]
+async fn get_comments(
+    pool: Data<DbPool>,
+    path: Path<(String, String)>,
+    query: Query<CommentParams>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let (team_id, crossword_id) = path.into_inner();
+    if let Err(error) = require_role(
+        pool.clone(),
+        team_id.clone(),
+        &caller.user,
+        TeamRole::Member,
+    )
+    .await
+    {
+        return build_error_response(error);
+    }
+    let query = query.into_inner();
+    let clue = match (query.clue, query.direction) {
+        (Some(clue), Some(direction)) => Some((clue, direction)),
+        (None, None) => None,
+        _ => return HttpResponse::BadRequest().body("clue and direction must be given together"),
+    };
+    match retrieve_clue_comments(pool, team_id, crossword_id, clue).await {
+        Ok(comments) => serde_json::to_string(&comments).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse comments to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ReplayParams {
+    speed: Option<f64>,
+    max_gap: Option<i64>,
+}
+
+#[get("/replay/{team_id}/{crossword_id}")]
+async fn get_replay(
+    pool: Data<DbPool>,
+    path: Path<(String, String)>,
+    query: Query<ReplayParams>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let (team_id, crossword_id) = path.into_inner();
+    if let Err(error) = require_role(
+        pool.clone(),
+        team_id.clone(),
+        &caller.user,
+        TeamRole::Member,
+    )
+    .await
+    {
+        return build_error_response(error);
+    }
+    if query
+        .speed
+        .is_some_and(|speed| !speed.is_finite() || speed <= 0.0)
+    {
+        return HttpResponse::BadRequest().body("speed must be greater than 0");
+    }
+    if query.max_gap.is_some_and(|max_gap| max_gap < 0) {
+        return HttpResponse::BadRequest().body("maxGap can't be negative");
+    }
+    let compression = ReplayCompression {
+        speed: query.speed,
+        max_gap: query.max_gap,
+    };
+    match retrieve_replay(pool, team_id, crossword_id, compression).await {
+        Ok(replay) => serde_json::to_string(&replay).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse replay to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[get("/completions/{team_id}")]
+async fn get_completions(
+    pool: Data<DbPool>,
+    path: Path<(String,)>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let team_id = path.into_inner().0;
+    if let Err(error) = require_role(
+        pool.clone(),
+        team_id.clone(),
+        &caller.user,
+        TeamRole::Member,
+    )
+    .await
+    {
+        return build_error_response(error);
+    }
+    match retrieve_completions(pool, team_id).await {
+        Ok(completions) => serde_json::to_string(&completions).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse completions to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[get("/teams/{team_id}/progress")]
+async fn get_progress(
+    pool: Data<DbPool>,
+    path: Path<(String,)>,
+    query: Query<ProgressQuery>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let team_id = path.into_inner().0;
+    if let Err(error) = require_role(
+        pool.clone(),
+        team_id.clone(),
+        &caller.user,
+        TeamRole::Member,
+    )
+    .await
+    {
+        return build_error_response(error);
+    }
+    match retrieve_progress(pool, team_id, query.into_inner()).await {
+        Ok(progress) => serde_json::to_string(&progress).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse progress to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[post("/teams")]
+async fn post_team(
+    pool: Data<DbPool>,
+    data: web::Json<NewTeamDto>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    match create_team(pool, data.into_inner().name, caller.user).await {
+        Ok(team) => serde_json::to_string(&team).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse team to a string"),
+            |x| HttpResponse::Created().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[get("/teams")]
+async fn get_teams(pool: Data<DbPool>, caller: AuthenticatedUser) -> impl Responder {
+    match retrieve_teams(pool, caller.user).await {
+        Ok(teams) => serde_json::to_string(&teams).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse teams to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[get("/teams/{team_id}")]
+async fn get_team(
+    pool: Data<DbPool>,
+    path: Path<(String,)>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let team_id = path.into_inner().0;
+    match retrieve_team(pool, team_id, caller.user).await {
+        Ok(team) => serde_json::to_string(&team).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse team to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[post("/teams/{team_id}/invite-code")]
+async fn post_invite_code(
+    pool: Data<DbPool>,
+    path: Path<(String,)>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let team_id = path.into_inner().0;
+    match reset_invite_code(pool, team_id, caller.user).await {
+        Ok(team) => serde_json::to_string(&team).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse team to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[post("/invites/{invite_code}")]
+async fn accept_invite(
+    pool: Data<DbPool>,
+    path: Path<(String,)>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let invite_code = path.into_inner().0;
+    match join_team(pool, invite_code, caller.user).await {
+        Ok(team) => serde_json::to_string(&team).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse team to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[put("/teams/{team_id}/members/{user}")]
+async fn put_member_role(
+    pool: Data<DbPool>,
+    path: Path<(String, String)>,
+    data: web::Json<RoleDto>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let (team_id, user) = path.into_inner();
+    match change_role(pool, team_id, caller.user, user, data.role).await {
+        Ok(team) => serde_json::to_string(&team).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse team to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[delete("/teams/{team_id}/members/{user}")]
+async fn delete_member(
+    pool: Data<DbPool>,
+    path: Path<(String, String)>,
+    caller: AuthenticatedUser,
+    srv: Data<Addr<MoveServer>>,
+) -> impl Responder {
+    let (team_id, user) = path.into_inner();
+    match remove_member(pool, team_id.clone(), caller.user, user.clone()).await {
+        Ok(()) => {
+            srv.do_send(MemberRemoved {
+                team: team_id,
+                user,
+            });
+            HttpResponse::NoContent().finish()
         }
+        Err(error) => build_error_response(error),
     }
-    serde_json::to_string(
-        &all_crosswords
-            .iter()
-            .map(|c| CrosswordMetadataWithHumanDate {
-                id: c.id.clone(),
-                series: c.series.clone(),
-                series_no: c.series_no,
-                date: c.date,
-                human_date: to_human_readable_date(c.date),
-            })
-            .collect::<Vec<CrosswordMetadataWithHumanDate>>(),
+}
+
+#[get("/teams/{team_id}/settings")]
+async fn get_team_settings(
+    pool: Data<DbPool>,
+    path: Path<(String,)>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let team_id = path.into_inner().0;
+    if let Err(error) = require_role(
+        pool.clone(),
+        team_id.clone(),
+        &caller.user,
+        TeamRole::Member,
     )
-    .map_or(
-        HttpResponse::BadRequest().body("Couldn't parse crossword to a string"),
+    .await
+    {
+        return build_error_response(error);
+    }
+    match retrieve_team_settings(pool, team_id).await {
+        Ok(settings) => serde_json::to_string(&settings).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse team settings to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[put("/teams/{team_id}/settings")]
+async fn put_team_settings(
+    pool: Data<DbPool>,
+    path: Path<(String,)>,
+    data: web::Json<TeamSettingsDto>,
+    caller: AuthenticatedUser,
+) -> impl Responder {
+    let team_id = path.into_inner().0;
+    if let Err(error) =
+        require_role(pool.clone(), team_id.clone(), &caller.user, TeamRole::Admin).await
+    {
+        return build_error_response(error);
+    }
+    match update_team_settings(pool, team_id, data.into_inner()).await {
+        Ok(settings) => serde_json::to_string(&settings).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse team settings to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[post("/accounts")]
+async fn create_account(
+    pool: Data<DbPool>,
+    token_keys: Data<TokenKeys>,
+    data: web::Json<RegistrationDto>,
+) -> impl Responder {
+    match register(pool, &token_keys, data.into_inner()).await {
+        Ok(session) => serde_json::to_string(&session).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse session to a string"),
+            |x| HttpResponse::Created().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[post("/sessions")]
+async fn create_session(
+    pool: Data<DbPool>,
+    token_keys: Data<TokenKeys>,
+    data: web::Json<LoginDto>,
+) -> impl Responder {
+    match login(pool, &token_keys, data.into_inner()).await {
+        Ok(session) => serde_json::to_string(&session).map_or(
+            HttpResponse::BadRequest().body("Couldn't parse session to a string"),
+            |x| HttpResponse::Ok().body(x),
+        ),
+        Err(error) => build_error_response(error),
+    }
+}
+
+#[get("/me")]
+async fn get_current_user(caller: AuthenticatedUser) -> impl Responder {
+    let account = AccountDto {
+        user: caller.user,
+        name: caller.name,
+    };
+    serde_json::to_string(&account).map_or(
+        HttpResponse::BadRequest().body("Couldn't parse account to a string"),
         |x| HttpResponse::Ok().body(x),
     )
 }
 
-#[get("/move/{team_id}/{crossword_id}/{user_id}")]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ConnectionParams {
+    last_seq: Option<i64>,
+}
+
+/// Joins a team's room for a crossword as the user the session token identifies, as long as they are a member.
+#[get("/move/{team_id}/{crossword_id}")]
 pub async fn start_connection(
     req: HttpRequest,
     stream: Payload,
-    path: Path<(String, String, String)>,
+    path: Path<(String, String)>,
+    query: Query<ConnectionParams>,
+    caller: AuthenticatedUser,
+    pool: Data<DbPool>,
     srv: Data<Addr<MoveServer>>,
 ) -> Result<HttpResponse, Error> {
+    let (team_id, crossword_id) = path.into_inner();
+    require_role(pool, team_id.clone(), &caller.user, TeamRole::Member).await?;
     let ws = WsSession::new(
         srv.get_ref().clone(),
-        path.2.clone(),
-        path.clone().0,
-        path.1.clone(),
+        caller.user,
+        caller.name,
+        team_id,
+        crossword_id,
+        query.last_seq,
     );
     start(ws, &req, stream)
 }
 
+/// The request line for the access log, with the value of any `token` query parameter, which WebSocket clients
+/// authenticate with, left out.
+fn redacted_request_line(req: &ServiceRequest) -> String {
+    let query = req
+        .query_string()
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.split_once('=') {
+            Some(("token", _)) => "token=[redacted]",
+            _ => param,
+        })
+        .collect::<Vec<&str>>()
+        .join("&");
+    let path = if query.is_empty() {
+        req.path().to_string()
+    } else {
+        format!("{}?{}", req.path(), query)
+    };
+    format!("{} {} {:?}", req.method(), path, req.version())
+}
+
 fn build_error_response(error: AppError) -> HttpResponse {
     HttpResponse::build(to_status_code(error.clone())).body(error.clone().to_string())
 }
 
+fn database_url() -> String {
+    std::env::var("DATABASE_URL").expect("DATABASE_URL should be set")
+}
+
+/// Secret that session tokens are signed with, shared by every instance.
+fn token_secret() -> String {
+    std::env::var("TOKEN_SECRET").expect("TOKEN_SECRET should be set")
+}
+
+/// How long a cursor can stay put before it is shown as idle, from `CURSOR_IDLE_SECONDS`.
+fn cursor_idle_after() -> Duration {
+    let seconds = std::env::var("CURSOR_IDLE_SECONDS")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .unwrap_or(60);
+    Duration::from_secs(seconds)
+}
+
 fn initialize_db_pool() -> DbPool {
-    let conn_spec = std::env::var("DATABASE_URL").expect("DATABASE_URL should be set");
+    let conn_spec = database_url();
     println!("Connecting to database at: {}", conn_spec);
     let manager = r2d2::ConnectionManager::<PgConnection>::new(conn_spec);
     r2d2::Pool::builder()
@@ -6,6 +6,7 @@ use chrono::NaiveDate;
 use diesel::Queryable;
 use serde::ser::SerializeStruct;
 use serde::{Deserialize, Serialize, Serializer};
+use uuid::Uuid;
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Queryable)]
 #[serde(rename_all = "camelCase")]
@@ -14,6 +15,7 @@ pub struct CrosswordMetadata {
     pub series: String,
     pub series_no: i64,
     pub date: i64,
+    pub setter: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, Queryable)]
@@ -24,6 +26,66 @@ pub struct CrosswordMetadataWithHumanDate {
     pub series_no: i64,
     pub date: i64,
     pub human_date: String,
+    pub setter: Option<String>,
+}
+
+/// What to sort crossword listings by.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CrosswordSort {
+    #[default]
+    Date,
+    Number,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Filters, sort order and page of a crossword listing. Dates are in milliseconds and ranges include both ends.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrosswordQuery {
+    /// Comma separated series to include, eg. `cryptic,prize`.
+    pub series: Option<String>,
+    pub date_from: Option<i64>,
+    pub date_to: Option<i64>,
+    /// Part of the setter's name, matched case insensitively.
+    pub setter: Option<String>,
+    pub number_from: Option<i64>,
+    pub number_to: Option<i64>,
+    #[serde(default)]
+    pub sort: CrosswordSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub limit: Option<i64>,
+    /// `nextCursor` from the previous page.
+    pub cursor: Option<String>,
+}
+
+/// One page of a crossword listing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrosswordPageDto {
+    pub crosswords: Vec<CrosswordMetadataWithHumanDate>,
+    /// Cursor for the page after this one, or `None` if this is the last.
+    pub next_cursor: Option<String>,
+}
+
+/// How sure the solver is of a cell's `value`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CellState {
+    #[default]
+    Confirmed,
+    /// Pencilled in as a guess.
+    Pencilled,
+    /// Filled in from the solution at the team's request.
+    Revealed,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -31,7 +93,17 @@ pub struct SolutionItemDto {
     pub x: i64,
     pub y: i64,
     pub value: String,
+    #[serde(default)]
+    pub state: CellState,
+    /// Letters the solver is still choosing between.
+    #[serde(default)]
+    pub candidates: Vec<String>,
+    #[serde(default)]
     pub modified_by: String,
+    #[serde(default)]
+    pub seq: i64,
+    #[serde(default)]
+    pub updated_at: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -39,6 +111,279 @@ pub struct CurrentCellDto {
     pub x: i64,
     pub y: i64,
     pub user: String,
+    /// Direction the user is solving in, if they have chosen one.
+    #[serde(default)]
+    pub direction: Option<Direction>,
+    /// Number of the clue in `direction` that the user is on.
+    #[serde(default)]
+    pub clue: Option<i64>,
+    /// Set once the cursor hasn't moved for a while.
+    #[serde(default)]
+    pub idle: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceDto {
+    pub user: String,
+    pub name: String,
+    pub current_cell: Option<CurrentCellDto>,
+    pub connected_since: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatMessageDto {
+    pub id: i64,
+    pub user: String,
+    pub name: String,
+    pub text: String,
+    /// Server time in milliseconds when the message was sent.
+    pub sent_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CellPositionDto {
+    pub x: i64,
+    pub y: i64,
+}
+
+/// Part of the grid that a check applies to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "scope", rename_all = "camelCase")]
+pub enum AssistTarget {
+    Cell { x: i64, y: i64 },
+    Clue { clue: i64, direction: Direction },
+    Grid,
+}
+
+/// Whether a reveal needs the agreement of everyone else in the room.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RevealPolicy {
+    /// Reveals happen as soon as they are proposed.
+    #[default]
+    Auto,
+    /// Every other user in the room has to accept a reveal.
+    Vote,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamSettingsDto {
+    pub reveal_policy: RevealPolicy,
+}
+
+/// What a member of a team may do, in increasing order of privilege.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TeamRole {
+    Member,
+    /// Can manage the invite code and remove members.
+    Admin,
+    /// Can do everything, including changing roles. Every team has exactly one.
+    Owner,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewTeamDto {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamMemberDto {
+    pub user: String,
+    pub name: String,
+    pub role: TeamRole,
+    pub joined_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamDto {
+    pub id: String,
+    pub name: String,
+    /// The caller's role in the team.
+    pub role: TeamRole,
+    /// Only shown to admins and the owner.
+    pub invite_code: Option<String>,
+    pub members: Vec<TeamMemberDto>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TeamSummaryDto {
+    pub id: String,
+    pub name: String,
+    pub role: TeamRole,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleDto {
+    pub role: TeamRole,
+}
+
+/// A reveal waiting on the votes of the other users in the room.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevealProposalDto {
+    pub id: Uuid,
+    pub target: AssistTarget,
+    pub proposed_by: String,
+    /// Users who have to accept the reveal.
+    pub voters: Vec<String>,
+    /// Voters who have accepted it so far.
+    pub accepted_by: Vec<String>,
+    /// Server time in milliseconds when the proposal lapses.
+    pub expires_at: i64,
+}
+
+/// One move in a replay of a solve.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayEditDto {
+    pub seq: i64,
+    pub user: String,
+    /// Milliseconds after the first move at which to play this one, after any compression.
+    pub at: i64,
+    /// When the move was made, in milliseconds.
+    pub made_at: i64,
+    pub cells: Vec<SolutionItemDto>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayDto {
+    /// When the first move was made, in milliseconds.
+    pub started_at: Option<i64>,
+    /// Length of the replay in milliseconds, after any compression.
+    pub duration: i64,
+    pub edits: Vec<ReplayEditDto>,
+}
+
+/// A team's finished solve of a crossword.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompletionDto {
+    pub crossword_id: String,
+    /// When the first move was made, in milliseconds.
+    pub started_at: Option<i64>,
+    /// When the grid was first found to be full and correct, in milliseconds.
+    pub completed_at: i64,
+    /// Number of checks and reveals used along the way.
+    pub assists: i64,
+    /// Everyone who made a move in the solve.
+    pub contributors: Vec<String>,
+}
+
+/// Whether a team has finished a crossword it has started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProgressState {
+    InProgress,
+    Completed,
+}
+
+/// How far a team has got with a crossword it has started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrosswordProgressDto {
+    pub crossword_id: String,
+    pub series: String,
+    pub series_no: i64,
+    pub date: i64,
+    /// Share of the white cells with a letter in them, pencilled or not, rounded down.
+    pub percent_filled: i64,
+    pub state: ProgressState,
+    pub completed_at: Option<i64>,
+    /// When a cell was last written, in milliseconds, or `None` if none have been written since cells were timestamped.
+    pub last_activity: Option<i64>,
+}
+
+/// Page of a team's progress.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressQuery {
+    pub limit: Option<i64>,
+    /// `nextCursor` from the previous page.
+    pub cursor: Option<String>,
+}
+
+/// One page of a team's progress.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgressPageDto {
+    pub progress: Vec<CrosswordProgressDto>,
+    /// Cursor for the page after this one, or `None` if this is the last.
+    pub next_cursor: Option<String>,
+}
+
+/// A team's shared solve timer. It runs from the first move while anyone is in the room, until someone pauses it or
+/// the crossword is finished.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimerDto {
+    /// When the first move was made, in milliseconds, or `None` if the timer hasn't started.
+    pub started_at: Option<i64>,
+    /// Milliseconds on the clock at server time `at`.
+    pub elapsed: i64,
+    pub at: i64,
+    /// Whether the clock has been running since `at`.
+    pub running: bool,
+    /// Whether someone has paused the timer, rather than it having stopped for an empty room or a finished solve.
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistrationDto {
+    pub username: String,
+    pub password: String,
+    /// Name shown to teammates, the username if not given.
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginDto {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDto {
+    pub user: String,
+    pub name: String,
+}
+
+/// A signed session token and who it identifies.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionDto {
+    pub token: String,
+    pub user: String,
+    pub name: String,
+    /// When the token stops being accepted, in milliseconds.
+    pub expires_at: i64,
+}
+
+/// Note left by a teammate on a clue.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClueCommentDto {
+    pub id: i64,
+    pub clue: i64,
+    pub direction: Direction,
+    pub user: String,
+    pub name: String,
+    pub text: String,
+    /// Server time in milliseconds when the comment was left.
+    pub created_at: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable)]
@@ -52,6 +397,9 @@ pub struct CrosswordDto {
     pub series_no: String,
     pub date: String,
     pub setter: String,
+    /// Whether solution letters have been left out.
+    #[serde(default)]
+    pub spoiler_free: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Queryable)]
@@ -1,9 +1,10 @@
 #![allow(unused)]
 #![allow(clippy::all)]
 
+use crate::models::api_models::CellState;
 use crate::schema::crossword;
 use chrono::NaiveDate;
-use diesel::{Insertable, Queryable};
+use diesel::{AsChangeset, Insertable, Queryable};
 use serde::{Deserialize, Serialize};
 #[derive(Queryable, Debug, Clone, Insertable)]
 #[diesel(table_name = crossword)]
@@ -22,6 +23,42 @@ pub struct Solution {
     pub crossword_for: String,
     pub team_for: String,
     pub solution_json: serde_json::Value,
+    pub seq: i64,
+    /// When a cell was last written, in milliseconds, or 0 if none have been written since cells were timestamped.
+    pub last_activity: i64,
+}
+
+use crate::schema::move_log;
+/// One user's move as it changed the stored solution. Never updated or deleted.
+#[derive(Queryable, Debug, Clone)]
+pub struct MoveLogEntry {
+    pub id: i64,
+    pub crossword_for: String,
+    pub team_for: String,
+    pub seq: i64,
+    pub user_id: String,
+    /// The cells the move replaced, with empty cells for those that hadn't been filled.
+    pub cells_before: serde_json::Value,
+    pub cells_after: serde_json::Value,
+    pub created_at: i64,
+    /// `move`, `reveal`, `undo` or `redo`, as in `MoveKind`.
+    pub kind: String,
+    /// For an undo or redo, the `seq` of the logged move it reverted.
+    pub reverts: Option<i64>,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = move_log)]
+pub struct NewMoveLogEntry {
+    pub crossword_for: String,
+    pub team_for: String,
+    pub seq: i64,
+    pub user_id: String,
+    pub cells_before: serde_json::Value,
+    pub cells_after: serde_json::Value,
+    pub created_at: i64,
+    pub kind: String,
+    pub reverts: Option<i64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -29,5 +66,193 @@ pub struct SolutionItem {
     pub x: i64,
     pub y: i64,
     pub value: String,
+    /// Whether `value` is confirmed or pencilled in.
+    #[serde(default)]
+    pub state: CellState,
+    /// Letters the solver is still choosing between.
+    #[serde(default)]
+    pub candidates: Vec<String>,
     pub modified_by: String,
+    /// Sequence number of the move that last wrote this cell, used as its logical clock.
+    #[serde(default)]
+    pub seq: i64,
+    /// Server time in milliseconds when this cell was last written.
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+impl SolutionItem {
+    /// A cell that hasn't been filled in.
+    pub fn empty(x: i64, y: i64) -> SolutionItem {
+        SolutionItem {
+            x,
+            y,
+            ..SolutionItem::default()
+        }
+    }
+
+    /// Whether both items hold the same entry, whoever wrote them.
+    pub fn same_content(&self, other: &SolutionItem) -> bool {
+        self.value == other.value
+            && self.state == other.state
+            && self.candidates == other.candidates
+    }
+
+    /// Whether this write should win over `other` for the same cell under last-writer-wins.
+    pub fn supersedes(&self, other: &SolutionItem) -> bool {
+        (self.seq, self.updated_at, &self.modified_by)
+            >= (other.seq, other.updated_at, &other.modified_by)
+    }
+}
+
+use crate::schema::chat_message;
+#[derive(Queryable, Debug, Clone)]
+pub struct ChatMessage {
+    pub id: i64,
+    pub crossword_for: String,
+    pub team_for: String,
+    pub user_id: String,
+    pub name: String,
+    pub text: String,
+    pub sent_at: i64,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = chat_message)]
+pub struct NewChatMessage {
+    pub crossword_for: String,
+    pub team_for: String,
+    pub user_id: String,
+    pub name: String,
+    pub text: String,
+    pub sent_at: i64,
+}
+
+use crate::schema::clue_comment;
+#[derive(Queryable, Debug, Clone)]
+pub struct ClueComment {
+    pub id: i64,
+    pub crossword_for: String,
+    pub team_for: String,
+    pub clue_number: i64,
+    /// `across` or `down`, as in `Clue::direction`.
+    pub direction: String,
+    pub user_id: String,
+    pub name: String,
+    pub text: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = clue_comment)]
+pub struct NewClueComment {
+    pub crossword_for: String,
+    pub team_for: String,
+    pub clue_number: i64,
+    pub direction: String,
+    pub user_id: String,
+    pub name: String,
+    pub text: String,
+    pub created_at: i64,
+}
+
+use crate::schema::assist_log;
+/// A check or other help a user asked for while solving.
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = assist_log)]
+pub struct NewAssistLogEntry {
+    pub crossword_for: String,
+    pub team_for: String,
+    pub user_id: String,
+    pub action: String,
+    pub target: serde_json::Value,
+    pub created_at: i64,
+}
+
+use crate::schema::team_settings;
+#[derive(Queryable, Debug, Clone, Insertable, AsChangeset)]
+#[diesel(table_name = team_settings)]
+pub struct TeamSettings {
+    pub team_id: String,
+    /// `auto` or `vote`, as in `RevealPolicy`.
+    pub reveal_policy: String,
+}
+
+use crate::schema::completion;
+/// A team's finished solve of a crossword.
+#[derive(Queryable, Debug, Clone, Insertable)]
+#[diesel(table_name = completion)]
+pub struct Completion {
+    pub crossword_for: String,
+    pub team_for: String,
+    /// When the first move was made, if the move log has it.
+    pub started_at: Option<i64>,
+    pub completed_at: i64,
+    /// Number of checks and reveals the team used.
+    pub assists: i64,
+    pub contributors: Vec<String>,
+}
+
+use crate::schema::solve_timer;
+/// A team's shared timer for a crossword.
+#[derive(Queryable, Debug, Clone, Insertable, AsChangeset)]
+#[diesel(table_name = solve_timer)]
+#[diesel(treat_none_as_null = true)]
+pub struct SolveTimer {
+    pub crossword_for: String,
+    pub team_for: String,
+    pub started_at: Option<i64>,
+    /// Milliseconds on the clock when it was last stopped, or when it started running again.
+    pub elapsed: i64,
+    /// When the timer started running, if it is.
+    pub running_since: Option<i64>,
+    pub paused: bool,
+}
+
+use crate::schema::account;
+#[derive(Queryable, Debug, Clone, Insertable)]
+#[diesel(table_name = account)]
+pub struct Account {
+    pub username: String,
+    pub display_name: String,
+    /// Argon2 hash in PHC string format.
+    pub password_hash: String,
+    pub created_at: i64,
+    /// Lets the user trigger crossword ingestion. Only ever set by hand in the database.
+    pub is_admin: bool,
+}
+
+use crate::schema::team;
+#[derive(Queryable, Debug, Clone, Insertable)]
+#[diesel(table_name = team)]
+pub struct Team {
+    pub id: String,
+    pub name: String,
+    /// Code that lets anyone who has it join the team.
+    pub invite_code: String,
+    pub created_at: i64,
+}
+
+use crate::schema::team_membership;
+#[derive(Queryable, Debug, Clone, Insertable)]
+#[diesel(table_name = team_membership)]
+pub struct TeamMembership {
+    pub team_id: String,
+    pub username: String,
+    /// `owner`, `admin` or `member`, as in `TeamRole`.
+    pub role: String,
+    pub joined_at: i64,
+}
+
+use crate::schema::ingestion_audit;
+/// Who triggered a crossword ingestion, and for what.
+#[derive(Debug, Clone, Insertable)]
+#[diesel(table_name = ingestion_audit)]
+pub struct NewIngestionAuditEntry {
+    pub triggered_by: String,
+    pub action: String,
+    pub series: Option<String>,
+    pub start_id: Option<i64>,
+    pub end_id: Option<i64>,
+    pub created_at: i64,
 }
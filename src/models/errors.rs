@@ -1,5 +1,6 @@
 use actix_web::error::BlockingError;
 use actix_web::http::StatusCode;
+use actix_web::ResponseError;
 use scraper::error::SelectorErrorKind;
 use std::fmt;
 use std::num::ParseIntError;
@@ -8,6 +9,13 @@ use std::num::ParseIntError;
 pub enum AppError {
     InternalServerError(String),
     CrosswordNotFound(String),
+    TeamNotFound(String),
+    BadRequest(String),
+    /// The caller hasn't proved who they are.
+    Unauthorized(String),
+    /// The caller is known but isn't allowed to do what they asked.
+    Forbidden(String),
+    Conflict(String),
 }
 
 impl fmt::Display for AppError {
@@ -19,6 +27,13 @@ impl fmt::Display for AppError {
             AppError::CrosswordNotFound(id) => {
                 write!(f, "Could not find crossword for id: {}", id)
             }
+            AppError::TeamNotFound(id) => {
+                write!(f, "Could not find team for id: {}", id)
+            }
+            AppError::BadRequest(message)
+            | AppError::Unauthorized(message)
+            | AppError::Forbidden(message)
+            | AppError::Conflict(message) => write!(f, "{}", message),
         }
     }
 }
@@ -27,6 +42,18 @@ pub fn to_status_code(error: AppError) -> StatusCode {
     match error {
         AppError::InternalServerError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         AppError::CrosswordNotFound(_) => StatusCode::NOT_FOUND,
+        AppError::TeamNotFound(_) => StatusCode::NOT_FOUND,
+        AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+        AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+        AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+        AppError::Conflict(_) => StatusCode::CONFLICT,
+    }
+}
+
+/// Lets extractors fail with an `AppError`, answered the same way as `build_error_response`.
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        to_status_code(self.clone())
     }
 }
 
@@ -42,6 +69,12 @@ impl From<serde_json::Error> for AppError {
     }
 }
 
+impl From<diesel::result::Error> for AppError {
+    fn from(error: diesel::result::Error) -> Self {
+        AppError::InternalServerError(error.to_string())
+    }
+}
+
 impl From<r2d2::Error> for AppError {
     fn from(error: r2d2::Error) -> Self {
         AppError::InternalServerError(error.to_string())
@@ -56,7 +89,7 @@ impl From<reqwest::Error> for AppError {
 
 impl From<SelectorErrorKind<'_>> for AppError {
     fn from(error: SelectorErrorKind) -> Self {
-        AppError::InternalServerError(format!("Invalid selector: {}", error.to_string()))
+        AppError::InternalServerError(format!("Invalid selector: {}", error))
     }
 }
 
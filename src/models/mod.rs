@@ -2,3 +2,4 @@ pub mod api_models;
 pub mod db_models;
 pub mod errors;
 pub mod guardian;
+pub mod ws_messages;
@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::models::api_models::{
+    AssistTarget, CellPositionDto, ChatMessageDto, ClueCommentDto, CompletionDto, CurrentCellDto,
+    Direction, PresenceDto, RevealProposalDto, SolutionItemDto, TimerDto,
+};
+
+/// Version of the `/move` socket protocol, sent as `v` on every frame.
+pub const PROTOCOL_VERSION: u64 = 1;
+
+/// Frames sent by clients over the `/move` socket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ClientMessage {
+    Move {
+        #[serde(default)]
+        client_move_id: Option<String>,
+        items: Vec<SolutionItemDto>,
+    },
+    /// Reverts the user's latest move that hasn't been undone.
+    Undo {
+        #[serde(default)]
+        client_move_id: Option<String>,
+    },
+    /// Reapplies the user's latest undone move.
+    Redo {
+        #[serde(default)]
+        client_move_id: Option<String>,
+    },
+    Cursor {
+        x: i64,
+        y: i64,
+        #[serde(default)]
+        direction: Option<Direction>,
+        #[serde(default)]
+        clue: Option<i64>,
+    },
+    Chat {
+        text: String,
+    },
+    /// Checks the team's letters in part of the grid against the solution.
+    Check {
+        target: AssistTarget,
+    },
+    /// Proposes revealing the solution for part of the grid.
+    Reveal {
+        target: AssistTarget,
+    },
+    /// Accepts or rejects a reveal someone else proposed.
+    RevealVote {
+        id: Uuid,
+        accept: bool,
+    },
+    /// Pauses the team's timer until someone resumes it.
+    PauseTimer,
+    ResumeTimer,
+    /// Leaves a note on a clue.
+    Comment {
+        clue: i64,
+        direction: Direction,
+        text: String,
+    },
+}
+
+/// Frames sent by the server over the `/move` socket.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum ServerMessage {
+    Snapshot {
+        seq: i64,
+        items: Vec<SolutionItemDto>,
+        /// Set once the team has finished the crossword.
+        completion: Option<CompletionDto>,
+        timer: TimerDto,
+    },
+    /// Moves missed since the `lastSeq` a reconnecting client resumed from.
+    Resume {
+        seq: i64,
+        moves: Vec<SequencedMove>,
+        completion: Option<CompletionDto>,
+        timer: TimerDto,
+    },
+    Move(SequencedMove),
+    /// Sent to the sender of a move once it has been persisted, with the cells it stored as the server stamped them.
+    /// Cells left out were superseded by a later write or already held what the move wrote.
+    Ack {
+        client_move_id: Option<String>,
+        seq: i64,
+        items: Vec<SolutionItemDto>,
+    },
+    /// Sent to the sender of a move that could not be persisted.
+    Rejected {
+        client_move_id: Option<String>,
+        message: String,
+    },
+    Cursor(CurrentCellDto),
+    /// A user's cursor is gone and should no longer be shown.
+    CursorRemoved {
+        user: String,
+    },
+    /// Everyone in the room, sent to a session when it connects.
+    Roster {
+        members: Vec<PresenceDto>,
+    },
+    /// A user's first session in the room has connected.
+    Join(PresenceDto),
+    /// A user's last session in the room has disconnected.
+    Leave {
+        user: String,
+    },
+    Chat(ChatMessageDto),
+    /// Recent chat in the room, oldest first, sent to a session when it connects.
+    ChatHistory {
+        messages: Vec<ChatMessageDto>,
+    },
+    Comment(ClueCommentDto),
+    /// Filled-in cells a check found to be right and wrong, sent to the session that asked.
+    CheckResult {
+        target: AssistTarget,
+        correct: Vec<CellPositionDto>,
+        incorrect: Vec<CellPositionDto>,
+    },
+    /// The shared timer has started, stopped or been paused or resumed.
+    Timer(TimerDto),
+    /// The grid has just been found to be full and correct.
+    Solved(CompletionDto),
+    /// A reveal waiting on votes, sent when it is proposed and whenever someone accepts it.
+    RevealProposal(RevealProposalDto),
+    /// A reveal has been accepted and carried out, or has been rejected or lapsed.
+    RevealResolved {
+        id: Uuid,
+        accepted: bool,
+    },
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SequencedMove {
+    pub seq: i64,
+    pub items: Vec<SolutionItemDto>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    InvalidJson,
+    UnsupportedVersion,
+    InvalidMessage,
+    InternalError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl From<ProtocolError> for ServerMessage {
+    fn from(error: ProtocolError) -> Self {
+        ServerMessage::Error {
+            code: error.code,
+            message: error.message,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, T> {
+    v: u64,
+    #[serde(flatten)]
+    body: &'a T,
+}
+
+impl ServerMessage {
+    pub fn error(code: ErrorCode, message: String) -> ServerMessage {
+        ServerMessage::Error { code, message }
+    }
+
+    /// Serializes the message as a versioned text frame.
+    pub fn to_frame(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&Envelope {
+            v: PROTOCOL_VERSION,
+            body: self,
+        })
+    }
+}
+
+/// Parses a text frame from a client, checking the protocol version before the payload.
+pub fn parse_client_message(text: &str) -> Result<ClientMessage, ProtocolError> {
+    let value: Value = serde_json::from_str(text).map_err(|e| ProtocolError {
+        code: ErrorCode::InvalidJson,
+        message: e.to_string(),
+    })?;
+    match value.get("v").and_then(Value::as_u64) {
+        Some(PROTOCOL_VERSION) => {}
+        Some(v) => {
+            return Err(ProtocolError {
+                code: ErrorCode::UnsupportedVersion,
+                message: format!("Unsupported protocol version: {}", v),
+            })
+        }
+        None => {
+            return Err(ProtocolError {
+                code: ErrorCode::UnsupportedVersion,
+                message: "Missing protocol version".to_string(),
+            })
+        }
+    }
+    serde_json::from_value(value).map_err(|e| ProtocolError {
+        code: ErrorCode::InvalidMessage,
+        message: e.to_string(),
+    })
+}
@@ -1,5 +1,64 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    account (username) {
+        username -> Varchar,
+        display_name -> Varchar,
+        password_hash -> Varchar,
+        created_at -> Int8,
+        is_admin -> Bool,
+    }
+}
+
+diesel::table! {
+    assist_log (id) {
+        id -> Int8,
+        crossword_for -> Varchar,
+        team_for -> Varchar,
+        user_id -> Varchar,
+        action -> Varchar,
+        target -> Jsonb,
+        created_at -> Int8,
+    }
+}
+
+diesel::table! {
+    chat_message (id) {
+        id -> Int8,
+        crossword_for -> Varchar,
+        team_for -> Varchar,
+        user_id -> Varchar,
+        name -> Varchar,
+        text -> Text,
+        sent_at -> Int8,
+    }
+}
+
+diesel::table! {
+    clue_comment (id) {
+        id -> Int8,
+        crossword_for -> Varchar,
+        team_for -> Varchar,
+        clue_number -> Int8,
+        direction -> Varchar,
+        user_id -> Varchar,
+        name -> Varchar,
+        text -> Text,
+        created_at -> Int8,
+    }
+}
+
+diesel::table! {
+    completion (crossword_for, team_for) {
+        crossword_for -> Varchar,
+        team_for -> Varchar,
+        started_at -> Nullable<Int8>,
+        completed_at -> Int8,
+        assists -> Int8,
+        contributors -> Array<Text>,
+    }
+}
+
 diesel::table! {
     crossword (id) {
         id -> Varchar,
@@ -10,12 +69,103 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    ingestion_audit (id) {
+        id -> Int8,
+        triggered_by -> Varchar,
+        action -> Varchar,
+        series -> Nullable<Varchar>,
+        start_id -> Nullable<Int8>,
+        end_id -> Nullable<Int8>,
+        created_at -> Int8,
+    }
+}
+
+diesel::table! {
+    move_log (id) {
+        id -> Int8,
+        crossword_for -> Varchar,
+        team_for -> Varchar,
+        seq -> Int8,
+        user_id -> Varchar,
+        cells_before -> Jsonb,
+        cells_after -> Jsonb,
+        created_at -> Int8,
+        kind -> Varchar,
+        reverts -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    room_event_payload (id) {
+        id -> Int8,
+        payload -> Text,
+        created_at -> Int8,
+    }
+}
+
 diesel::table! {
     solution (crossword_for, team_for) {
         crossword_for -> Varchar,
         team_for -> Varchar,
         solution_json -> Jsonb,
+        seq -> Int8,
+        last_activity -> Int8,
     }
 }
 
-diesel::allow_tables_to_appear_in_same_query!(crossword, solution,);
+diesel::table! {
+    solve_timer (crossword_for, team_for) {
+        crossword_for -> Varchar,
+        team_for -> Varchar,
+        started_at -> Nullable<Int8>,
+        elapsed -> Int8,
+        running_since -> Nullable<Int8>,
+        paused -> Bool,
+    }
+}
+
+diesel::table! {
+    team (id) {
+        id -> Varchar,
+        name -> Varchar,
+        invite_code -> Varchar,
+        created_at -> Int8,
+    }
+}
+
+diesel::table! {
+    team_membership (team_id, username) {
+        team_id -> Varchar,
+        username -> Varchar,
+        role -> Varchar,
+        joined_at -> Int8,
+    }
+}
+
+diesel::table! {
+    team_settings (team_id) {
+        team_id -> Varchar,
+        reveal_policy -> Varchar,
+    }
+}
+
+diesel::joinable!(team_membership -> account (username));
+diesel::joinable!(team_membership -> team (team_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    account,
+    assist_log,
+    chat_message,
+    clue_comment,
+    completion,
+    crossword,
+    ingestion_audit,
+    move_log,
+    room_event_payload,
+    solution,
+    solve_timer,
+    team,
+    team_membership,
+    team_settings,
+);
@@ -0,0 +1,45 @@
+use actix_web::web;
+use diesel::result::DatabaseErrorKind;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+
+use crate::models::db_models::Account;
+use crate::models::errors::AppError;
+use crate::schema::account::dsl::{account, username};
+use crate::DbPool;
+
+pub async fn get_account(
+    pool: web::Data<DbPool>,
+    username_to_find: String,
+) -> actix_web::Result<Option<Account>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        account
+            .filter(username.eq(username_to_find))
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+pub async fn store_account(
+    pool: web::Data<DbPool>,
+    new_account: Account,
+) -> actix_web::Result<usize, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        let taken = new_account.username.clone();
+        diesel::insert_into(account)
+            .values(new_account)
+            .execute(&mut conn)
+            .map_err(|e| match e {
+                diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                    AppError::Conflict(format!("Username {} is taken", taken))
+                }
+                e => AppError::InternalServerError(e.to_string()),
+            })
+    })
+    .await?
+}
@@ -0,0 +1,22 @@
+use actix_web::web;
+use diesel::RunQueryDsl;
+
+use crate::models::db_models::NewAssistLogEntry;
+use crate::models::errors::AppError;
+use crate::schema::assist_log::dsl::assist_log;
+use crate::DbPool;
+
+pub async fn store_assist_log_entry(
+    pool: web::Data<DbPool>,
+    new_assist_log_entry: NewAssistLogEntry,
+) -> actix_web::Result<usize, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        diesel::insert_into(assist_log)
+            .values(new_assist_log_entry)
+            .execute(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
@@ -0,0 +1,96 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::models::api_models::{AssistTarget, CellPositionDto, SolutionItemDto};
+use crate::models::db_models::NewAssistLogEntry;
+use crate::models::errors::AppError;
+use crate::models::guardian::GuardianCrossword;
+use crate::services::assist_db_actions::store_assist_log_entry;
+use crate::services::crossword_service::{entry_cells, find_entry, solution_letters};
+use crate::DbPool;
+use actix_web::web;
+use chrono::Utc;
+
+/// Cells of a crossword that a check found to be right and wrong.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CheckResult {
+    pub correct: Vec<CellPositionDto>,
+    pub incorrect: Vec<CellPositionDto>,
+}
+
+/// The white cells a target covers along with their solution letters, ordered by position.
+pub fn target_letters(
+    guardian_crossword: &GuardianCrossword,
+    target: &AssistTarget,
+) -> Result<Vec<(CellPositionDto, String)>, String> {
+    let letters = solution_letters(guardian_crossword);
+    if letters.is_empty() {
+        return Err("Solutions aren't available for this crossword yet".to_string());
+    }
+    let positions: BTreeSet<(i64, i64)> = match target {
+        AssistTarget::Cell { x, y } => {
+            if !letters.contains_key(&(*x, *y)) {
+                return Err(format!("({}, {}) isn't a white cell", x, y));
+            }
+            BTreeSet::from([(*x, *y)])
+        }
+        AssistTarget::Clue { clue, direction } => {
+            let entry = find_entry(guardian_crossword, *clue, direction)
+                .ok_or_else(|| format!("No {:?} clue {}", direction, clue))?;
+            entry_cells(entry).into_iter().collect()
+        }
+        AssistTarget::Grid => letters.keys().copied().collect(),
+    };
+    positions
+        .into_iter()
+        .map(|(x, y)| {
+            letters
+                .get(&(x, y))
+                .map(|letter| (CellPositionDto { x, y }, letter.clone()))
+                .ok_or_else(|| "Solutions aren't available for this crossword yet".to_string())
+        })
+        .collect()
+}
+
+/// Compares the filled-in cells of a target with the crossword's solution, leaving out empty cells.
+pub fn check(
+    guardian_crossword: &GuardianCrossword,
+    solution: &HashMap<(i64, i64), SolutionItemDto>,
+    target: &AssistTarget,
+) -> Result<CheckResult, String> {
+    let mut result = CheckResult::default();
+    for (position, letter) in target_letters(guardian_crossword, target)? {
+        let Some(item) = solution
+            .get(&(position.x, position.y))
+            .filter(|item| !item.value.is_empty())
+        else {
+            continue;
+        };
+        if item.value.eq_ignore_ascii_case(&letter) {
+            result.correct.push(position);
+        } else {
+            result.incorrect.push(position);
+        }
+    }
+    Ok(result)
+}
+
+/// Records that a user asked for help, eg. `check`, on part of the grid.
+pub async fn log_assist(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+    user_id: String,
+    action: &str,
+    target: AssistTarget,
+) -> Result<(), AppError> {
+    let new_assist_log_entry = NewAssistLogEntry {
+        crossword_for: crossword_id,
+        team_for: team_id,
+        user_id,
+        action: action.to_string(),
+        target: serde_json::to_value(&target)?,
+        created_at: Utc::now().timestamp_millis(),
+    };
+    store_assist_log_entry(pool, new_assist_log_entry).await?;
+    Ok(())
+}
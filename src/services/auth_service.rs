@@ -0,0 +1,330 @@
+use actix_web::dev::Payload;
+use actix_web::web::{self, Data, Query};
+use actix_web::{FromRequest, HttpRequest};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use chrono::Utc;
+use futures::future::{ready, LocalBoxFuture, Ready};
+use futures::FutureExt;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use password_hash::rand_core::OsRng;
+use password_hash::SaltString;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::models::api_models::{LoginDto, RegistrationDto, SessionDto};
+use crate::models::db_models::Account;
+use crate::models::errors::AppError;
+use crate::services::account_db_actions::{get_account, store_account};
+use crate::services::util::validate_text;
+use crate::DbPool;
+
+/// How long a session token is accepted for.
+const TOKEN_LIFETIME_DAYS: i64 = 30;
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+const MAX_USERNAME_LENGTH: usize = 32;
+
+const MAX_DISPLAY_NAME_LENGTH: usize = 50;
+
+/// Keys that session tokens are signed and verified with.
+pub struct TokenKeys {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl TokenKeys {
+    pub fn new(secret: &[u8]) -> TokenKeys {
+        TokenKeys {
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    name: String,
+    /// Expiry as a unix timestamp in seconds.
+    exp: i64,
+}
+
+/// The admin API key, from `ADMIN_API_KEY`. Without one, only users with the admin role can make admin requests.
+pub struct AdminApiKey(pub Option<String>);
+
+/// Whoever made a request only admins are allowed to make, either a user with the admin role or a caller with the
+/// admin API key in its `X-Api-Key` header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Admin {
+    /// The admin's username, or `api-key` for callers with the API key.
+    pub actor: String,
+}
+
+/// The user a request was made by, from the session token in its `Authorization: Bearer` header or, for clients
+/// that can't set headers such as browser WebSockets, its `token` query parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthenticatedUser {
+    pub user: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct TokenParams {
+    token: Option<String>,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(authenticate(req))
+    }
+}
+
+impl FromRequest for Admin {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let api_key = req
+            .app_data::<Data<AdminApiKey>>()
+            .and_then(|key| key.0.clone());
+        let given_key = req
+            .headers()
+            .get("X-Api-Key")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        if let Some(given_key) = given_key {
+            let admin = match api_key {
+                // Compared in constant time so response times don't give the key away
+                Some(api_key) if bool::from(api_key.as_bytes().ct_eq(given_key.as_bytes())) => {
+                    Ok(Admin {
+                        actor: "api-key".to_string(),
+                    })
+                }
+                _ => Err(AppError::Unauthorized("Invalid API key".to_string())),
+            };
+            return ready(admin).boxed_local();
+        }
+        let pool = req.app_data::<Data<DbPool>>().cloned();
+        let caller = authenticate(req);
+        async move {
+            let caller = caller?;
+            let pool = pool.ok_or_else(|| {
+                AppError::InternalServerError("Database pool hasn't been configured".to_string())
+            })?;
+            match get_account(pool, caller.user.clone()).await? {
+                Some(account) if account.is_admin => Ok(Admin {
+                    actor: account.username,
+                }),
+                _ => Err(AppError::Forbidden("Only admins can do that".to_string())),
+            }
+        }
+        .boxed_local()
+    }
+}
+
+fn authenticate(req: &HttpRequest) -> Result<AuthenticatedUser, AppError> {
+    let keys = req.app_data::<Data<TokenKeys>>().ok_or_else(|| {
+        AppError::InternalServerError("Token keys haven't been configured".to_string())
+    })?;
+    let from_header = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.to_string());
+    let token = from_header
+        .or_else(|| {
+            Query::<TokenParams>::from_query(req.query_string())
+                .ok()
+                .and_then(|params| params.into_inner().token)
+        })
+        .ok_or_else(|| AppError::Unauthorized("Missing session token".to_string()))?;
+    verify_token(keys, &token)
+}
+
+pub fn verify_token(keys: &TokenKeys, token: &str) -> Result<AuthenticatedUser, AppError> {
+    let claims = decode::<Claims>(token, &keys.decoding, &Validation::default())
+        .map_err(|e| AppError::Unauthorized(format!("Invalid session token: {}", e)))?
+        .claims;
+    Ok(AuthenticatedUser {
+        user: claims.sub,
+        name: claims.name,
+    })
+}
+
+fn issue_token(keys: &TokenKeys, account: &Account) -> Result<SessionDto, AppError> {
+    let expires_at = Utc::now() + chrono::Duration::days(TOKEN_LIFETIME_DAYS);
+    let claims = Claims {
+        sub: account.username.clone(),
+        name: account.display_name.clone(),
+        exp: expires_at.timestamp(),
+    };
+    let token = encode(&Header::default(), &claims, &keys.encoding)
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    Ok(SessionDto {
+        token,
+        user: account.username.clone(),
+        name: account.display_name.clone(),
+        expires_at: expires_at.timestamp_millis(),
+    })
+}
+
+/// Creates an account and signs its first session token.
+pub async fn register(
+    pool: web::Data<DbPool>,
+    keys: &TokenKeys,
+    registration: RegistrationDto,
+) -> Result<SessionDto, AppError> {
+    let username = validate_username(&registration.username).map_err(AppError::BadRequest)?;
+    let display_name = match registration.display_name {
+        Some(display_name) => validate_text(&display_name, MAX_DISPLAY_NAME_LENGTH, "Display name")
+            .map_err(AppError::BadRequest)?,
+        None => username.clone(),
+    };
+    validate_password(&registration.password).map_err(AppError::BadRequest)?;
+    let password = registration.password;
+    // Hashing is deliberately slow, so keep it off the server thread
+    let password_hash = web::block(move || {
+        Argon2::default()
+            .hash_password(password.as_bytes(), &SaltString::generate(&mut OsRng))
+            .map(|hash| hash.to_string())
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await??;
+    let account = Account {
+        username,
+        display_name,
+        password_hash,
+        created_at: Utc::now().timestamp_millis(),
+        is_admin: false,
+    };
+    store_account(pool, account.clone()).await?;
+    issue_token(keys, &account)
+}
+
+/// Signs a session token for a user whose password matches.
+pub async fn login(
+    pool: web::Data<DbPool>,
+    keys: &TokenKeys,
+    login: LoginDto,
+) -> Result<SessionDto, AppError> {
+    let invalid = || AppError::Unauthorized("Invalid username or password".to_string());
+    let account = get_account(pool, login.username)
+        .await?
+        .ok_or_else(invalid)?;
+    let password_hash = account.password_hash.clone();
+    let password = login.password;
+    let matches = web::block(move || {
+        PasswordHash::new(&password_hash)
+            .map(|hash| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok()
+            })
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await??;
+    if !matches {
+        return Err(invalid());
+    }
+    issue_token(keys, &account)
+}
+
+/// Usernames identify users everywhere, so they are limited to letters, digits, `-` and `_`.
+fn validate_username(username: &str) -> Result<String, String> {
+    let username = validate_text(username, MAX_USERNAME_LENGTH, "Username")?;
+    if !username
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("Username can only contain letters, digits, - and _".to_string());
+    }
+    Ok(username)
+}
+
+fn validate_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(format!(
+            "Password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> Account {
+        Account {
+            username: "bob".to_string(),
+            display_name: "Bob".to_string(),
+            password_hash: String::new(),
+            created_at: 0,
+            is_admin: false,
+        }
+    }
+
+    #[test]
+    fn issued_tokens_verify_as_their_user() {
+        let keys = TokenKeys::new(b"secret");
+        let session = issue_token(&keys, &account()).expect("token should be issued");
+        let user = verify_token(&keys, &session.token).expect("token should verify");
+        assert_eq!(
+            user,
+            AuthenticatedUser {
+                user: "bob".to_string(),
+                name: "Bob".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn refuses_expired_tokens() {
+        let keys = TokenKeys::new(b"secret");
+        let claims = Claims {
+            sub: "bob".to_string(),
+            name: "Bob".to_string(),
+            exp: (Utc::now() - chrono::Duration::hours(1)).timestamp(),
+        };
+        let token = encode(&Header::default(), &claims, &keys.encoding).expect("should encode");
+        assert!(matches!(
+            verify_token(&keys, &token),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn refuses_tokens_signed_with_another_secret() {
+        let session =
+            issue_token(&TokenKeys::new(b"other"), &account()).expect("token should be issued");
+        assert!(matches!(
+            verify_token(&TokenKeys::new(b"secret"), &session.token),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn accepts_usernames_of_letters_digits_dashes_and_underscores() {
+        assert_eq!(validate_username("a-b_1"), Ok("a-b_1".to_string()));
+    }
+
+    #[test]
+    fn refuses_usernames_with_other_characters_or_too_long() {
+        assert!(validate_username("a b").is_err());
+        assert!(validate_username("zoë").is_err());
+        assert!(validate_username(&"a".repeat(MAX_USERNAME_LENGTH + 1)).is_err());
+        assert!(validate_username(&"a".repeat(MAX_USERNAME_LENGTH)).is_ok());
+    }
+
+    #[test]
+    fn passwords_need_the_minimum_length() {
+        assert!(validate_password(&"p".repeat(MIN_PASSWORD_LENGTH - 1)).is_err());
+        assert!(validate_password(&"p".repeat(MIN_PASSWORD_LENGTH)).is_ok());
+    }
+}
@@ -0,0 +1,45 @@
+use actix_web::web;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::models::db_models::{ChatMessage, NewChatMessage};
+use crate::models::errors::AppError;
+use crate::schema::chat_message::dsl::{chat_message, crossword_for, id, team_for};
+use crate::DbPool;
+
+pub async fn store_chat_message(
+    pool: web::Data<DbPool>,
+    new_chat_message: NewChatMessage,
+) -> actix_web::Result<ChatMessage, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        diesel::insert_into(chat_message)
+            .values(new_chat_message)
+            .get_result(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// The most recent `limit` messages in a room, oldest first.
+pub async fn get_recent_chat_messages(
+    pool: web::Data<DbPool>,
+    crossword_id: String,
+    team_id: String,
+    limit: i64,
+) -> actix_web::Result<Vec<ChatMessage>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    let mut messages: Vec<ChatMessage> = web::block(move || {
+        let mut conn = pool.get()?;
+        chat_message
+            .filter(crossword_for.eq(crossword_id))
+            .filter(team_for.eq(team_id))
+            .order(id.desc())
+            .limit(limit)
+            .load(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await??;
+    messages.reverse();
+    Ok(messages)
+}
@@ -0,0 +1,57 @@
+use crate::models::api_models::ChatMessageDto;
+use crate::models::db_models::{ChatMessage, NewChatMessage};
+use crate::models::errors::AppError;
+use crate::services::chat_db_actions::{get_recent_chat_messages, store_chat_message};
+use crate::services::util::validate_text;
+use crate::DbPool;
+use actix_web::web;
+use chrono::Utc;
+
+/// Longest chat message accepted, in characters.
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 500;
+
+/// Trims a chat message, rejecting it if nothing is left or it is too long.
+pub fn validate_chat_text(text: &str) -> Result<String, String> {
+    validate_text(text, MAX_CHAT_MESSAGE_LENGTH, "Chat message")
+}
+
+/// Timestamps and stores a chat message sent in a room.
+pub async fn send_chat_message(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+    user_id: String,
+    name: String,
+    text: String,
+) -> Result<ChatMessageDto, AppError> {
+    let new_chat_message = NewChatMessage {
+        crossword_for: crossword_id,
+        team_for: team_id,
+        user_id,
+        name,
+        text,
+        sent_at: Utc::now().timestamp_millis(),
+    };
+    let stored = store_chat_message(pool, new_chat_message).await?;
+    Ok(to_chat_message_dto(stored))
+}
+
+pub async fn get_chat_history(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+    limit: usize,
+) -> Result<Vec<ChatMessageDto>, AppError> {
+    let messages = get_recent_chat_messages(pool, crossword_id, team_id, limit as i64).await?;
+    Ok(messages.into_iter().map(to_chat_message_dto).collect())
+}
+
+fn to_chat_message_dto(chat_message: ChatMessage) -> ChatMessageDto {
+    ChatMessageDto {
+        id: chat_message.id,
+        user: chat_message.user_id,
+        name: chat_message.name,
+        text: chat_message.text,
+        sent_at: chat_message.sent_at,
+    }
+}
@@ -0,0 +1,51 @@
+use actix_web::web;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+
+use crate::models::db_models::{ClueComment, NewClueComment};
+use crate::models::errors::AppError;
+use crate::schema::clue_comment::dsl::{
+    clue_comment, clue_number, crossword_for, direction, id, team_for,
+};
+use crate::DbPool;
+
+pub async fn store_clue_comment(
+    pool: web::Data<DbPool>,
+    new_clue_comment: NewClueComment,
+) -> actix_web::Result<ClueComment, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        diesel::insert_into(clue_comment)
+            .values(new_clue_comment)
+            .get_result(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// Comments left by a team on a crossword, oldest first, optionally only those on one clue.
+pub async fn get_clue_comments(
+    pool: web::Data<DbPool>,
+    crossword_id: String,
+    team_id: String,
+    clue: Option<(i64, String)>,
+) -> actix_web::Result<Vec<ClueComment>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        let mut query = clue_comment
+            .filter(crossword_for.eq(crossword_id))
+            .filter(team_for.eq(team_id))
+            .into_boxed();
+        if let Some((number, clue_direction)) = clue {
+            query = query
+                .filter(clue_number.eq(number))
+                .filter(direction.eq(clue_direction));
+        }
+        query
+            .order(id.asc())
+            .load(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
@@ -0,0 +1,86 @@
+use crate::models::api_models::{ClueCommentDto, Direction};
+use crate::models::db_models::{ClueComment, NewClueComment};
+use crate::models::errors::AppError;
+use crate::services::comment_db_actions::{get_clue_comments, store_clue_comment};
+use crate::services::util::validate_text;
+use crate::DbPool;
+use actix_web::web;
+use chrono::Utc;
+
+/// Longest comment accepted, in characters.
+pub const MAX_COMMENT_LENGTH: usize = 500;
+
+/// Trims a comment, rejecting it if nothing is left or it is too long.
+pub fn validate_comment_text(text: &str) -> Result<String, String> {
+    validate_text(text, MAX_COMMENT_LENGTH, "Comment")
+}
+
+/// Timestamps and stores a comment left on a clue.
+#[allow(clippy::too_many_arguments)]
+pub async fn add_clue_comment(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+    clue: i64,
+    direction: Direction,
+    user_id: String,
+    name: String,
+    text: String,
+) -> Result<ClueCommentDto, AppError> {
+    let new_clue_comment = NewClueComment {
+        crossword_for: crossword_id,
+        team_for: team_id,
+        clue_number: clue,
+        direction: direction_to_db(&direction).to_string(),
+        user_id,
+        name,
+        text,
+        created_at: Utc::now().timestamp_millis(),
+    };
+    let stored = store_clue_comment(pool, new_clue_comment).await?;
+    to_clue_comment_dto(stored)
+}
+
+/// Comments a team has left on a crossword, optionally only those on one clue.
+pub async fn retrieve_clue_comments(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+    clue: Option<(i64, Direction)>,
+) -> Result<Vec<ClueCommentDto>, AppError> {
+    let clue = clue.map(|(number, direction)| (number, direction_to_db(&direction).to_string()));
+    get_clue_comments(pool, crossword_id, team_id, clue)
+        .await?
+        .into_iter()
+        .map(to_clue_comment_dto)
+        .collect()
+}
+
+fn direction_to_db(direction: &Direction) -> &'static str {
+    match direction {
+        Direction::Across => "across",
+        Direction::Down => "down",
+    }
+}
+
+fn to_clue_comment_dto(clue_comment: ClueComment) -> Result<ClueCommentDto, AppError> {
+    let direction = match clue_comment.direction.as_str() {
+        "across" => Direction::Across,
+        "down" => Direction::Down,
+        other => {
+            return Err(AppError::InternalServerError(format!(
+                "Unknown clue direction: {}",
+                other
+            )))
+        }
+    };
+    Ok(ClueCommentDto {
+        id: clue_comment.id,
+        clue: clue_comment.clue_number,
+        direction,
+        user: clue_comment.user_id,
+        name: clue_comment.name,
+        text: clue_comment.text,
+        created_at: clue_comment.created_at,
+    })
+}
@@ -0,0 +1,98 @@
+use actix_web::web;
+use diesel::dsl::{count_star, min};
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+
+use crate::models::db_models::Completion;
+use crate::models::errors::AppError;
+use crate::schema::{assist_log, completion, move_log};
+use crate::DbPool;
+
+pub async fn get_completion(
+    pool: web::Data<DbPool>,
+    crossword_id: String,
+    team_id: String,
+) -> actix_web::Result<Option<Completion>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        completion::table
+            .filter(completion::crossword_for.eq(crossword_id))
+            .filter(completion::team_for.eq(team_id))
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+pub async fn get_completions_for_team(
+    pool: web::Data<DbPool>,
+    team_id: String,
+) -> actix_web::Result<Vec<Completion>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        completion::table
+            .filter(completion::team_for.eq(team_id))
+            .order(completion::completed_at.desc())
+            .load(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// Records a solve as finished, tallying its assists and contributors from the logs, unless it already has been.
+/// Returns the stored completion either way.
+pub async fn store_completion(
+    pool: web::Data<DbPool>,
+    crossword_id: String,
+    team_id: String,
+    completed_at: i64,
+    final_writers: Vec<String>,
+) -> actix_web::Result<Completion, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        conn.transaction(|conn| {
+            let assists: i64 = assist_log::table
+                .filter(assist_log::crossword_for.eq(crossword_id.clone()))
+                .filter(assist_log::team_for.eq(team_id.clone()))
+                .select(count_star())
+                .first(conn)?;
+            let started_at: Option<i64> = move_log::table
+                .filter(move_log::crossword_for.eq(crossword_id.clone()))
+                .filter(move_log::team_for.eq(team_id.clone()))
+                .select(min(move_log::created_at))
+                .first(conn)?;
+            let mut contributors: Vec<String> = move_log::table
+                .filter(move_log::crossword_for.eq(crossword_id.clone()))
+                .filter(move_log::team_for.eq(team_id.clone()))
+                .select(move_log::user_id)
+                .distinct()
+                .load(conn)?;
+            // The last moves may still be queued for the move log
+            contributors.extend(final_writers);
+            contributors.sort();
+            contributors.dedup();
+
+            diesel::insert_into(completion::table)
+                .values(&Completion {
+                    crossword_for: crossword_id.clone(),
+                    team_for: team_id.clone(),
+                    started_at,
+                    completed_at,
+                    assists,
+                    contributors,
+                })
+                .on_conflict((completion::crossword_for, completion::team_for))
+                .do_nothing()
+                .execute(conn)?;
+            completion::table
+                .filter(completion::crossword_for.eq(crossword_id))
+                .filter(completion::team_for.eq(team_id))
+                .first(conn)
+        })
+        .map_err(|e: diesel::result::Error| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
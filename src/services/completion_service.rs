@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use crate::models::api_models::{CellState, CompletionDto, SolutionItemDto};
+use crate::models::db_models::Completion;
+use crate::models::errors::AppError;
+use crate::models::guardian::GuardianCrossword;
+use crate::services::completion_db_actions::{
+    get_completion, get_completions_for_team, store_completion,
+};
+use crate::services::crossword_service::solution_letters;
+use crate::DbPool;
+use actix_web::web;
+use chrono::Utc;
+
+/// Whether every white cell holds its solution letter, ignoring pencilled guesses.
+pub fn is_complete(
+    guardian_crossword: &GuardianCrossword,
+    solution: &HashMap<(i64, i64), SolutionItemDto>,
+) -> bool {
+    let letters = solution_letters(guardian_crossword);
+    !letters.is_empty()
+        && letters.iter().all(|(position, letter)| {
+            solution.get(position).is_some_and(|item| {
+                item.state != CellState::Pencilled && item.value.eq_ignore_ascii_case(letter)
+            })
+        })
+}
+
+/// Records that a team has finished a crossword, given who wrote the letters in the finished grid, keeping the first completion if it was already recorded.
+pub async fn record_completion(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+    final_writers: Vec<String>,
+) -> Result<CompletionDto, AppError> {
+    let completion = store_completion(
+        pool,
+        crossword_id,
+        team_id,
+        Utc::now().timestamp_millis(),
+        final_writers,
+    )
+    .await?;
+    Ok(to_completion_dto(completion))
+}
+
+pub async fn retrieve_completion(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+) -> Result<Option<CompletionDto>, AppError> {
+    let completion = get_completion(pool, crossword_id, team_id).await?;
+    Ok(completion.map(to_completion_dto))
+}
+
+/// Crosswords a team has finished, most recent first.
+pub async fn retrieve_completions(
+    pool: web::Data<DbPool>,
+    team_id: String,
+) -> Result<Vec<CompletionDto>, AppError> {
+    let completions = get_completions_for_team(pool, team_id).await?;
+    Ok(completions.into_iter().map(to_completion_dto).collect())
+}
+
+fn to_completion_dto(completion: Completion) -> CompletionDto {
+    CompletionDto {
+        crossword_id: completion.crossword_for,
+        started_at: completion.started_at,
+        completed_at: completion.completed_at,
+        assists: completion.assists,
+        contributors: completion.contributors,
+    }
+}
@@ -1,11 +1,17 @@
 #![allow(unused)]
 
 use actix_web::web;
+use diesel::dsl::{RetrieveAsObject, RetrieveAsText};
 use diesel::row::NamedRow;
-use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
+use diesel::{
+    BoolExpressionMethods, EscapeExpressionMethods, ExpressionMethods, NullableExpressionMethods,
+    PgAnyJsonExpressionMethods, PgTextExpressionMethods, QueryDsl, QueryResult, RunQueryDsl,
+};
 use serde_json::Value;
 
-use crate::models::api_models::{Clue, CrosswordDto, CrosswordMetadata};
+use crate::models::api_models::{
+    Clue, CrosswordDto, CrosswordMetadata, CrosswordQuery, CrosswordSort, SortOrder,
+};
 use crate::models::db_models::Crossword;
 use crate::models::errors::AppError;
 use crate::models::errors::AppError::InternalServerError;
@@ -39,18 +45,100 @@ pub async fn get_crossword_metadata_for_series(
         let mut conn = pool.get()?;
         crossword
             .filter(series.eq(series_for))
-            .select((id, series, series_no, date))
+            .select((id, series, series_no, date, setter().nullable()))
             .load(&mut conn)
             .map_err(|e| AppError::InternalServerError(e.to_string()))
     })
     .await?
 }
 
+/// Crossword metadata matching a listing's filters, in its sort order, starting after the crossword with the sort key
+/// and id in `after`. Ties on the sort key are broken by id so that pages never overlap.
+pub async fn get_crossword_metadata_page(
+    pool: web::Data<DbPool>,
+    query: CrosswordQuery,
+    series_in: Vec<String>,
+    after: Option<(i64, String)>,
+    limit: i64,
+) -> actix_web::Result<Vec<CrosswordMetadata>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        let mut listing = crossword
+            .select((id, series, series_no, date, setter().nullable()))
+            .into_boxed();
+        if !series_in.is_empty() {
+            listing = listing.filter(series.eq_any(series_in));
+        }
+        if let Some(date_from) = query.date_from {
+            listing = listing.filter(date.ge(date_from));
+        }
+        if let Some(date_to) = query.date_to {
+            listing = listing.filter(date.le(date_to));
+        }
+        if let Some(number_from) = query.number_from {
+            listing = listing.filter(series_no.ge(number_from));
+        }
+        if let Some(number_to) = query.number_to {
+            listing = listing.filter(series_no.le(number_to));
+        }
+        if let Some(setter_name) = query.setter {
+            listing = listing.filter(setter().ilike(containing(&setter_name)).escape('\\'));
+        }
+        if let Some((key, after_id)) = after {
+            listing = match (query.sort, query.order) {
+                (CrosswordSort::Date, SortOrder::Asc) => {
+                    listing.filter(date.gt(key).or(date.eq(key).and(id.gt(after_id))))
+                }
+                (CrosswordSort::Date, SortOrder::Desc) => {
+                    listing.filter(date.lt(key).or(date.eq(key).and(id.lt(after_id))))
+                }
+                (CrosswordSort::Number, SortOrder::Asc) => {
+                    listing.filter(series_no.gt(key).or(series_no.eq(key).and(id.gt(after_id))))
+                }
+                (CrosswordSort::Number, SortOrder::Desc) => {
+                    listing.filter(series_no.lt(key).or(series_no.eq(key).and(id.lt(after_id))))
+                }
+            };
+        }
+        listing = match (query.sort, query.order) {
+            (CrosswordSort::Date, SortOrder::Asc) => listing.order((date.asc(), id.asc())),
+            (CrosswordSort::Date, SortOrder::Desc) => listing.order((date.desc(), id.desc())),
+            (CrosswordSort::Number, SortOrder::Asc) => listing.order((series_no.asc(), id.asc())),
+            (CrosswordSort::Number, SortOrder::Desc) => {
+                listing.order((series_no.desc(), id.desc()))
+            }
+        };
+        listing
+            .limit(limit)
+            .load(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// A `LIKE` pattern matching text that contains `text` anywhere, with its wildcards and escape character escaped so
+/// they only match themselves.
+fn containing(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// The setter's name, which is only stored in the crossword's JSON and is missing for some crosswords.
+fn setter() -> RetrieveAsText<RetrieveAsObject<crossword_json, &'static str>, &'static str> {
+    crossword_json
+        .retrieve_as_object("creator")
+        .retrieve_as_text("name")
+}
+
 pub async fn get_crossword_for_series_and_number(
     pool: web::Data<DbPool>,
     series_no_for: String,
     series_for: String,
-) -> actix_web::Result<CrosswordDto, AppError> {
+) -> actix_web::Result<GuardianCrossword, AppError> {
     // use web::block to offload blocking Diesel queries without blocking server thread
     let result: Value = web::block(move || {
         let mut conn = pool.get()?;
@@ -62,8 +150,24 @@ pub async fn get_crossword_for_series_and_number(
             .map_err(|_| AppError::CrosswordNotFound(series_no_for.clone()))
     })
     .await??;
-    let guardian_crossword: GuardianCrossword = serde_json::from_value(result)?;
-    Ok(guardian_to_crossword_dto(guardian_crossword))
+    Ok(serde_json::from_value(result)?)
+}
+
+pub async fn get_crossword_by_id(
+    pool: web::Data<DbPool>,
+    crossword_id: String,
+) -> actix_web::Result<GuardianCrossword, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    let result: Value = web::block(move || {
+        let mut conn = pool.get()?;
+        crossword
+            .filter(id.eq(crossword_id.clone()))
+            .select(crossword_json)
+            .first(&mut conn)
+            .map_err(|_| AppError::CrosswordNotFound(crossword_id.clone()))
+    })
+    .await??;
+    Ok(serde_json::from_value(result)?)
 }
 
 pub async fn store_crosswords(
@@ -80,3 +184,21 @@ pub async fn store_crosswords(
     })
     .await?
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setter_patterns_match_the_text_anywhere() {
+        assert_eq!(containing("Paul"), "%Paul%");
+        assert_eq!(containing(""), "%%");
+    }
+
+    #[test]
+    fn setter_patterns_escape_wildcards_and_the_escape_character() {
+        assert_eq!(containing("100%"), "%100\\%%");
+        assert_eq!(containing("a_b"), "%a\\_b%");
+        assert_eq!(containing("back\\slash"), "%back\\\\slash%");
+    }
+}
@@ -2,40 +2,56 @@ extern crate futures;
 extern crate serde;
 
 use actix_web::web;
+use chrono::Utc;
 use futures::future;
 use itertools::Itertools;
 use reqwest::header::{HeaderMap, HeaderValue};
 use scraper::Html;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::num::ParseIntError;
 use uuid::Uuid;
 
 use crate::models::api_models::Cell::{Black, White};
-use crate::models::api_models::{Cell, Clue, ClueId, CrosswordDto, Direction};
-use crate::models::db_models::Crossword;
+use crate::models::api_models::{
+    Cell, Clue, ClueId, CrosswordDto, CrosswordMetadata, CrosswordMetadataWithHumanDate,
+    CrosswordPageDto, CrosswordQuery, CrosswordSort, Direction,
+};
+use crate::models::db_models::{Crossword, NewIngestionAuditEntry};
 use crate::models::errors::AppError;
 use crate::models::guardian::{
     GuardianCrossword, GuardianCrosswordData, GuardianDirection, GuardianEntry,
 };
-use crate::services::crossword_db_actions::{get_crossword_nos_for_series, store_crosswords};
+use crate::services::crossword_db_actions::{
+    get_crossword_for_series_and_number, get_crossword_metadata_page, get_crossword_nos_for_series,
+    store_crosswords,
+};
+use crate::services::ingestion_db_actions::store_ingestion_audit_entry;
 use crate::services::util::to_human_readable_date;
 use crate::DbPool;
 
 type InterimClue = (ClueId, Option<i64>, String);
 
+/// Results in a page when the query doesn't say.
+const DEFAULT_PAGE_SIZE: i64 = 50;
+
+const MAX_PAGE_SIZE: i64 = 200;
+
+/// Most crosswords a bulk update can ask for, since each one is scraped from the Guardian.
+const MAX_BULK_UPDATE_RANGE: i64 = 100;
+
 pub async fn scrape_crossword(series: &str, id: String) -> Result<GuardianCrossword, AppError> {
     println!("Scraping {series} crossword: {id}",);
     let url = format!("https://www.theguardian.com/crosswords/{}/{}", series, id);
     let document = get_document(url).await?;
     let selector = scraper::Selector::parse("[name=CrosswordComponent]")?;
-    let element = document.select(&selector).last();
+    let element = document.select(&selector).next_back();
     match element {
         Some(e) => {
             let json = e
                 .value()
                 .attr("props")
-                .map_or(Err("No attribute found".to_string()), Ok)?;
+                .ok_or("No attribute found".to_string())?;
             let result: GuardianCrosswordData = serde_json::from_str(json)?;
             Ok(result.data)
         }
@@ -89,8 +105,7 @@ async fn get_recent_crossword_nos(series: &str, page: &i32) -> Result<Vec<i64>,
             let a = doc.select(&selector);
 
             let crossword_nos: Result<Vec<i64>, ParseIntError> = a
-                .map(|s| s.value().attr("href"))
-                .flatten()
+                .filter_map(|s| s.value().attr("href"))
                 .map(|s| s.to_string())
                 .filter(|url| url.starts_with(relative_url.as_str()) && !url.ends_with("#comments"))
                 .map(|url| {
@@ -110,12 +125,29 @@ async fn get_recent_crossword_nos(series: &str, page: &i32) -> Result<Vec<i64>,
     }
 }
 
+/// Guards against bulk updates that are empty or would scrape too many crosswords at once.
+pub fn validate_bulk_range(from_id: i64, to_id: i64) -> Result<(), AppError> {
+    if to_id <= from_id {
+        return Err(AppError::BadRequest(
+            "end_id must be greater than start_id".to_string(),
+        ));
+    }
+    if to_id - from_id > MAX_BULK_UPDATE_RANGE {
+        return Err(AppError::BadRequest(format!(
+            "Can't update more than {} crosswords at once",
+            MAX_BULK_UPDATE_RANGE
+        )));
+    }
+    Ok(())
+}
+
 pub async fn bulk_update_series(
     pool: web::Data<DbPool>,
     series: &str,
     from_id: &i64,
     to_id: &i64,
 ) -> Result<String, AppError> {
+    validate_bulk_range(*from_id, *to_id)?;
     let existing_crosswords_nos: Vec<i64> =
         get_crossword_nos_for_series(pool.clone(), series.to_string()).await?;
 
@@ -154,6 +186,101 @@ pub async fn bulk_update_series(
     Ok("Successfully scraped new crosswords".to_string())
 }
 
+/// Records who triggered an ingestion, and the series and range of crossword numbers it was for.
+pub async fn record_ingestion(
+    pool: web::Data<DbPool>,
+    triggered_by: String,
+    action: &str,
+    series: Option<String>,
+    range: Option<(i64, i64)>,
+) -> Result<(), AppError> {
+    let entry = NewIngestionAuditEntry {
+        triggered_by,
+        action: action.to_string(),
+        series,
+        start_id: range.map(|(start_id, _)| start_id),
+        end_id: range.map(|(_, end_id)| end_id),
+        created_at: Utc::now().timestamp_millis(),
+    };
+    store_ingestion_audit_entry(pool, entry).await?;
+    Ok(())
+}
+
+/// One page of the crosswords matching a listing query, with the cursor for the next page if there is one.
+pub async fn list_crosswords(
+    pool: web::Data<DbPool>,
+    query: CrosswordQuery,
+    known_series: &[&str],
+) -> Result<CrosswordPageDto, AppError> {
+    let limit = page_size(query.limit)?;
+    let series_in: Vec<String> = query
+        .series
+        .iter()
+        .flat_map(|series| series.split(','))
+        .map(|series| series.trim().to_lowercase())
+        .filter(|series| !series.is_empty())
+        .collect();
+    if let Some(unknown) = series_in
+        .iter()
+        .find(|series| !known_series.contains(&series.as_str()))
+    {
+        return Err(AppError::BadRequest(format!("Unknown series: {}", unknown)));
+    }
+    let after = query.cursor.as_deref().map(parse_cursor).transpose()?;
+    let sort = query.sort;
+    // Ask for one more than the page holds to find out whether there is another page
+    let mut crosswords =
+        get_crossword_metadata_page(pool, query, series_in, after, limit + 1).await?;
+    let next_cursor = if crosswords.len() as i64 > limit {
+        crosswords.truncate(limit as usize);
+        crosswords.last().map(|last| to_cursor(last, sort))
+    } else {
+        None
+    };
+    Ok(CrosswordPageDto {
+        crosswords: crosswords
+            .into_iter()
+            .map(|c| CrosswordMetadataWithHumanDate {
+                human_date: to_human_readable_date(c.date),
+                id: c.id,
+                series: c.series,
+                series_no: c.series_no,
+                date: c.date,
+                setter: c.setter,
+            })
+            .collect(),
+        next_cursor,
+    })
+}
+
+/// Cursors are the last crossword's sort key and id, so the next page starts straight after it.
+fn to_cursor(crossword: &CrosswordMetadata, sort: CrosswordSort) -> String {
+    let key = match sort {
+        CrosswordSort::Date => crossword.date,
+        CrosswordSort::Number => crossword.series_no,
+    };
+    format!("{}_{}", key, crossword.id)
+}
+
+/// How many results a page holds, `limit` if the query asked for an allowed size.
+pub fn page_size(limit: Option<i64>) -> Result<i64, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    Ok(limit)
+}
+
+pub fn parse_cursor(cursor: &str) -> Result<(i64, String), AppError> {
+    cursor
+        .split_once('_')
+        .and_then(|(key, after_id)| Some((key.parse().ok()?, after_id.to_string())))
+        .ok_or_else(|| AppError::BadRequest(format!("Invalid cursor: {}", cursor)))
+}
+
 pub async fn update_crosswords(
     pool: web::Data<DbPool>,
     series: &str,
@@ -185,7 +312,7 @@ pub async fn update_crosswords(
     let updated_crosswords = store_crosswords(pool.clone(), new_crosswords?).await?;
     Ok(format!(
         "Successfully scraped {} new crosswords",
-        updated_crosswords.to_string()
+        updated_crosswords
     ))
 }
 
@@ -232,7 +359,121 @@ pub fn guardian_to_crossword_dto(guardian_crossword: GuardianCrossword) -> Cross
         setter: guardian_crossword
             .creator
             .map_or("".to_string(), |c| c.name),
+        spoiler_free: false,
+    }
+}
+
+/// Looks up a crossword for a client, leaving out its solution letters if asked to or if the Guardian hasn't published
+/// them yet.
+pub async fn get_crossword_dto(
+    pool: web::Data<DbPool>,
+    series: String,
+    series_no: String,
+    spoiler_free: Option<bool>,
+) -> Result<CrosswordDto, AppError> {
+    let guardian_crossword = get_crossword_for_series_and_number(pool, series_no, series).await?;
+    let solutions_published = solutions_published(&guardian_crossword);
+    let crossword_dto = guardian_to_crossword_dto(guardian_crossword);
+    if spoiler_free.unwrap_or(false) || !solutions_published {
+        return Ok(without_solutions(crossword_dto));
     }
+    Ok(crossword_dto)
+}
+
+/// Whether the Guardian has published a crossword's solutions. Until then nothing may give them away, including
+/// checks, since checking letters one at a time would.
+pub fn solutions_published(guardian_crossword: &GuardianCrossword) -> bool {
+    guardian_crossword.date_solution_available <= Utc::now().timestamp_millis()
+}
+
+fn without_solutions(crossword_dto: CrosswordDto) -> CrosswordDto {
+    CrosswordDto {
+        cells: crossword_dto
+            .cells
+            .into_iter()
+            .map(|cell| match cell {
+                White { number, .. } => White {
+                    number,
+                    letter: "".to_string(),
+                },
+                Black => Black,
+            })
+            .collect(),
+        clues: crossword_dto
+            .clues
+            .into_iter()
+            .map(|clue| Clue {
+                solution: None,
+                ..clue
+            })
+            .collect(),
+        spoiler_free: true,
+        ..crossword_dto
+    }
+}
+
+/// The entry in `direction` that covers the cell at `x`, `y`, if it is numbered `number` when one is given.
+pub fn entry_covering<'a>(
+    guardian_crossword: &'a GuardianCrossword,
+    x: i64,
+    y: i64,
+    direction: &Direction,
+    number: Option<i64>,
+) -> Option<&'a GuardianEntry> {
+    guardian_crossword.entries.iter().find(|entry| {
+        let (along, across) = match entry.direction {
+            GuardianDirection::Across => (x - entry.position.x, y - entry.position.y),
+            GuardianDirection::Down => (y - entry.position.y, x - entry.position.x),
+        };
+        guardian_to_dto_direction(entry.direction.clone()) == *direction
+            && number.is_none_or(|number| number == entry.number)
+            && across == 0
+            && (0..entry.length).contains(&along)
+    })
+}
+
+/// The crossword's clue numbered `number` in `direction`, if it has one.
+pub fn find_entry<'a>(
+    guardian_crossword: &'a GuardianCrossword,
+    number: i64,
+    direction: &Direction,
+) -> Option<&'a GuardianEntry> {
+    guardian_crossword.entries.iter().find(|entry| {
+        entry.number == number && guardian_to_dto_direction(entry.direction.clone()) == *direction
+    })
+}
+
+/// Positions of the cells an entry covers, from its first letter to its last.
+pub fn entry_cells(entry: &GuardianEntry) -> Vec<(i64, i64)> {
+    (0..entry.length)
+        .map(|i| match entry.direction {
+            GuardianDirection::Across => (entry.position.x + i, entry.position.y),
+            GuardianDirection::Down => (entry.position.x, entry.position.y + i),
+        })
+        .collect()
+}
+
+/// Positions of the cells that any entry covers, which are the grid's white cells.
+pub fn white_cells(guardian_crossword: &GuardianCrossword) -> HashSet<(i64, i64)> {
+    guardian_crossword
+        .entries
+        .iter()
+        .flat_map(entry_cells)
+        .collect()
+}
+
+/// Letter of every white cell whose entries have a published solution.
+pub fn solution_letters(guardian_crossword: &GuardianCrossword) -> HashMap<(i64, i64), String> {
+    guardian_crossword
+        .entries
+        .iter()
+        .filter_map(|entry| entry.solution.as_ref().map(|solution| (entry, solution)))
+        .flat_map(|(entry, solution)| {
+            entry_cells(entry)
+                .into_iter()
+                .zip(solution.chars().map(|letter| letter.to_string()))
+        })
+        .collect()
 }
 
 fn to_interim_clue(entry: GuardianEntry, columns: i64) -> Vec<(i64, InterimClue)> {
@@ -283,14 +524,14 @@ fn guardian_to_dto_direction(direction: GuardianDirection) -> Direction {
 }
 
 fn get_cell(clue_items: Option<&Vec<InterimClue>>) -> Cell {
-    match clue_items.clone() {
+    match clue_items {
         None => Black,
         Some(clues) => {
-            let first_clue = clues.get(0);
+            let first_clue = clues.first();
             let second_clue = clues.get(1);
             let number = first_clue
-                .and_then(|(_, n, _s)| n.clone())
-                .or_else(|| second_clue.and_then(|(_, n, _s)| n.clone()));
+                .and_then(|(_, n, _s)| *n)
+                .or_else(|| second_clue.and_then(|(_, n, _s)| *n));
             first_clue
                 .map(|(_, _, s)| White {
                     number,
@@ -300,3 +541,65 @@ fn get_cell(clue_items: Option<&Vec<InterimClue>>) -> Cell {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(id: &str, series_no: i64, date: i64) -> CrosswordMetadata {
+        CrosswordMetadata {
+            id: id.to_string(),
+            series: "quick".to_string(),
+            series_no,
+            date,
+            setter: None,
+        }
+    }
+
+    #[test]
+    fn cursors_hold_the_sort_key_and_id() {
+        let crossword = metadata("quick/16789", 16789, 1_700_000_000_000);
+        assert_eq!(
+            to_cursor(&crossword, CrosswordSort::Date),
+            "1700000000000_quick/16789"
+        );
+        assert_eq!(
+            to_cursor(&crossword, CrosswordSort::Number),
+            "16789_quick/16789"
+        );
+    }
+
+    #[test]
+    fn cursors_parse_back_to_what_they_were_made_from() {
+        let crossword = metadata("weekend_special/12", 12, -5);
+        for sort in [CrosswordSort::Date, CrosswordSort::Number] {
+            let (key, id) =
+                parse_cursor(&to_cursor(&crossword, sort)).expect("cursor should parse");
+            let expected_key = match sort {
+                CrosswordSort::Date => -5,
+                CrosswordSort::Number => 12,
+            };
+            assert_eq!((key, id.as_str()), (expected_key, "weekend_special/12"));
+        }
+    }
+
+    #[test]
+    fn bulk_ranges_must_hold_between_one_and_the_most_crosswords() {
+        assert!(validate_bulk_range(1, 2).is_ok());
+        assert!(validate_bulk_range(1, 1 + MAX_BULK_UPDATE_RANGE).is_ok());
+        assert!(validate_bulk_range(1, 1).is_err());
+        assert!(validate_bulk_range(5, 1).is_err());
+        assert!(validate_bulk_range(1, 2 + MAX_BULK_UPDATE_RANGE).is_err());
+    }
+
+    #[test]
+    fn refuses_malformed_cursors() {
+        for cursor in ["", "123", "abc_quick/1", "_quick/1", "1.5_quick/1"] {
+            assert!(
+                matches!(parse_cursor(cursor), Err(AppError::BadRequest(_))),
+                "{:?} should be refused",
+                cursor
+            );
+        }
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::{HashMap, HashSet};
+use std::thread;
+use std::time::Duration;
+
+use crate::models::api_models::{
+    ChatMessageDto, ClueCommentDto, CurrentCellDto, PresenceDto, RevealProposalDto, TimerDto,
+};
+use crate::models::errors::AppError;
+use crate::models::ws_messages::SequencedMove;
+use crate::schema::room_event_payload;
+use crate::services::ws_server::{MoveServer, RoomKey};
+use crate::DbPool;
+use actix::prelude::*;
+use actix_web::web;
+use chrono::Utc;
+use diesel::sql_types::Text;
+use diesel::{Connection, ExpressionMethods, PgConnection, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Postgres channel that room events are published on.
+const CHANNEL: &str = "room_events";
+
+/// How often the listener checks for notifications.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long the listener waits before reconnecting after losing its connection.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Largest notification sent inline. Postgres rejects payloads of 8000 bytes or more, so larger events, such as a
+/// whole-grid reveal, are stored in `room_event_payload` and only their id is sent.
+const MAX_INLINE_PAYLOAD: usize = 7900;
+
+/// How long stored payloads are kept for listeners to read, in milliseconds.
+const STORED_PAYLOAD_LIFETIME: i64 = 60_000;
+
+/// Something that happened in a room that sessions on other instances need to see.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RoomEvent {
+    Move(SequencedMove),
+    Cursor(CurrentCellDto),
+    /// A user's first session in the room on the publishing instance has connected.
+    Join(PresenceDto),
+    /// A user's last session in the room on the publishing instance has disconnected.
+    Leave {
+        user: String,
+    },
+    /// A chat message that has been stored.
+    Chat(ChatMessageDto),
+    /// A clue comment that has been stored.
+    Comment(ClueCommentDto),
+    /// A reveal proposed on the publishing instance, whose room counts the votes.
+    RevealProposal(RevealProposalDto),
+    /// A vote on a reveal proposed on another instance.
+    RevealVote {
+        id: Uuid,
+        user: String,
+        accept: bool,
+    },
+    RevealResolved {
+        id: Uuid,
+        accepted: bool,
+    },
+    /// The room's timer as it now stands.
+    Timer(TimerDto),
+    /// A room has started on the publishing instance and asks the others who is connected to them.
+    PresenceRequest,
+    /// Sent regularly while the publishing instance has users in the room, listing them, so that others can tell
+    /// when an instance has gone away without saying who left.
+    Heartbeat {
+        users: Vec<String>,
+    },
+    /// The publishing instance couldn't persist moves it had already sent, so the solution should be reloaded.
+    Reload,
+    /// A user has been removed from the team. Published for the whole team, with no crossword, rather than one room.
+    MemberRemoved {
+        user: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Notification {
+    instance: Uuid,
+    team: String,
+    crossword: String,
+    event: RoomEvent,
+}
+
+/// What is sent over NOTIFY: either the notification itself, or the id of the row it was stored in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Payload {
+    Stored { stored: i64 },
+    Inline(Notification),
+}
+
+/// Publish an event from a room on this instance to every other instance
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Publish {
+    pub room: RoomKey,
+    pub event: RoomEvent,
+}
+
+/// Event published by a room on another instance
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct RemoteEvent {
+    pub instance: Uuid,
+    pub room: RoomKey,
+    pub event: RoomEvent,
+}
+
+/// Publishes room events over Postgres NOTIFY so that rooms for the same team and crossword on other instances see them.
+///
+/// Each room's events are queued and published in order, a batch per round trip, while rooms publish independently
+/// of each other, so one busy room or a slow database doesn't hold up every other room.
+pub struct Fanout {
+    instance: Uuid,
+    pool: DbPool,
+    /// Events waiting to be published, per room.
+    queued: HashMap<RoomKey, Vec<RoomEvent>>,
+    /// Rooms with a batch being published.
+    publishing: HashSet<RoomKey>,
+}
+
+impl Fanout {
+    pub fn new(instance: Uuid, pool: DbPool) -> Fanout {
+        Fanout {
+            instance,
+            pool,
+            queued: HashMap::new(),
+            publishing: HashSet::new(),
+        }
+    }
+
+    /// Queues an event for a room. A cursor update replaces the same user's cursor update if it is still queued, so a
+    /// backlog of cursor moves can't build up.
+    fn enqueue(&mut self, room: RoomKey, event: RoomEvent) {
+        let queue = self.queued.entry(room).or_default();
+        if let RoomEvent::Cursor(cursor) = &event {
+            let queued_cursor = queue.iter_mut().find(
+                |queued| matches!(queued, RoomEvent::Cursor(queued) if queued.user == cursor.user),
+            );
+            if let Some(queued_cursor) = queued_cursor {
+                *queued_cursor = event;
+                return;
+            }
+        }
+        queue.push(event);
+    }
+
+    /// Publishes everything queued for a room in one transaction, then carries on with whatever was queued meanwhile.
+    fn publish_queued(&mut self, room: RoomKey, ctx: &mut Context<Self>) {
+        let events = self.queued.remove(&room).unwrap_or_default();
+        if events.is_empty() {
+            self.publishing.remove(&room);
+            return;
+        }
+        self.publishing.insert(room.clone());
+        let notifications: Vec<Notification> = events
+            .into_iter()
+            .map(|event| Notification {
+                instance: self.instance,
+                team: room.0.clone(),
+                crossword: room.1.clone(),
+                event,
+            })
+            .collect();
+        let pool = self.pool.clone();
+        async move {
+            let payloads = notifications
+                .iter()
+                .map(serde_json::to_string)
+                .collect::<Result<Vec<String>, serde_json::Error>>()?;
+            web::block(move || {
+                let mut conn = pool.get()?;
+                conn.transaction(|conn| {
+                    for payload in payloads {
+                        notify(conn, payload)?;
+                    }
+                    Ok(())
+                })
+                .map_err(|e: diesel::result::Error| AppError::InternalServerError(e.to_string()))
+            })
+            .await?
+        }
+        .into_actor(self)
+        .map(move |result, act, ctx| {
+            if let Err(e) = result {
+                println!("Error publishing room event: {}", e);
+            }
+            act.publish_queued(room, ctx);
+        })
+        .spawn(ctx);
+    }
+}
+
+/// Sends a notification, storing it first if it is too large to send inline. Notifications are only delivered when
+/// the transaction commits, by which time the stored row can be read.
+fn notify(conn: &mut PgConnection, payload: String) -> Result<(), diesel::result::Error> {
+    let payload = if payload.len() > MAX_INLINE_PAYLOAD {
+        let now = Utc::now().timestamp_millis();
+        diesel::delete(room_event_payload::table)
+            .filter(room_event_payload::created_at.lt(now - STORED_PAYLOAD_LIFETIME))
+            .execute(conn)?;
+        let stored: i64 = diesel::insert_into(room_event_payload::table)
+            .values((
+                room_event_payload::payload.eq(payload),
+                room_event_payload::created_at.eq(now),
+            ))
+            .returning(room_event_payload::id)
+            .get_result(conn)?;
+        serde_json::to_string(&Payload::Stored { stored })
+            .map_err(|e| diesel::result::Error::SerializationError(Box::new(e)))?
+    } else {
+        payload
+    };
+    diesel::sql_query("SELECT pg_notify($1, $2)")
+        .bind::<Text, _>(CHANNEL)
+        .bind::<Text, _>(payload)
+        .execute(conn)?;
+    Ok(())
+}
+
+impl Actor for Fanout {
+    type Context = Context<Self>;
+}
+
+impl Handler<Publish> for Fanout {
+    type Result = ();
+
+    fn handle(&mut self, msg: Publish, ctx: &mut Context<Self>) {
+        let room = msg.room;
+        self.enqueue(room.clone(), msg.event);
+        if !self.publishing.contains(&room) {
+            self.publish_queued(room, ctx);
+        }
+    }
+}
+
+/// Listens for events published by other instances on a dedicated connection and hands them to the `MoveServer`.
+pub fn listen(database_url: String, instance: Uuid, server: Addr<MoveServer>) {
+    thread::spawn(move || loop {
+        if let Err(e) = listen_until_error(&database_url, instance, &server) {
+            println!("Error listening for room events: {}", e);
+        }
+        thread::sleep(RECONNECT_DELAY);
+    });
+}
+
+fn listen_until_error(
+    database_url: &str,
+    instance: Uuid,
+    server: &Addr<MoveServer>,
+) -> Result<(), AppError> {
+    let mut conn = PgConnection::establish(database_url)
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    diesel::sql_query(format!("LISTEN {}", CHANNEL)).execute(&mut conn)?;
+    loop {
+        let received = conn
+            .notifications_iter()
+            .collect::<Result<Vec<_>, diesel::result::Error>>()?;
+        for received in received {
+            let notification = match read_notification(&mut conn, &received.payload) {
+                Ok(notification) => notification,
+                Err(e) => {
+                    println!("Error reading room event: {}", e);
+                    continue;
+                }
+            };
+            if notification.instance != instance {
+                server.do_send(RemoteEvent {
+                    instance: notification.instance,
+                    room: (notification.team, notification.crossword),
+                    event: notification.event,
+                });
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Parses a notification, reading it from `room_event_payload` if it was too large to send inline.
+fn read_notification(conn: &mut PgConnection, payload: &str) -> Result<Notification, AppError> {
+    match serde_json::from_str(payload)? {
+        Payload::Inline(notification) => Ok(notification),
+        Payload::Stored { stored } => {
+            let payload: String = room_event_payload::table
+                .find(stored)
+                .select(room_event_payload::payload)
+                .first(conn)?;
+            Ok(serde_json::from_str(&payload)?)
+        }
+    }
+}
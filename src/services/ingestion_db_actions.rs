@@ -0,0 +1,22 @@
+use actix_web::web;
+use diesel::RunQueryDsl;
+
+use crate::models::db_models::NewIngestionAuditEntry;
+use crate::models::errors::AppError;
+use crate::schema::ingestion_audit::dsl::ingestion_audit;
+use crate::DbPool;
+
+pub async fn store_ingestion_audit_entry(
+    pool: web::Data<DbPool>,
+    new_ingestion_audit_entry: NewIngestionAuditEntry,
+) -> actix_web::Result<usize, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        diesel::insert_into(ingestion_audit)
+            .values(new_ingestion_audit_entry)
+            .execute(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
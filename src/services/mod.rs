@@ -1,7 +1,25 @@
+pub mod account_db_actions;
+pub mod assist_db_actions;
+pub mod assist_service;
+pub mod auth_service;
+pub mod chat_db_actions;
+pub mod chat_service;
+pub mod comment_db_actions;
+pub mod comment_service;
+pub mod completion_db_actions;
+pub mod completion_service;
 pub mod crossword_db_actions;
 pub mod crossword_service;
+pub mod fanout;
+pub mod ingestion_db_actions;
 pub mod solution_db_actions;
 pub mod solution_service;
+pub mod solution_writer;
+pub mod team_db_actions;
+pub mod team_service;
+pub mod timer_db_actions;
+pub mod timer_service;
 pub mod util;
+pub mod ws_room;
 pub mod ws_server;
 pub mod ws_session;
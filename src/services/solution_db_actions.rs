@@ -3,15 +3,22 @@
 use actix_web::web;
 use diesel::row::NamedRow;
 use diesel::OptionalExtension;
-use diesel::{ExpressionMethods, QueryDsl, QueryResult, RunQueryDsl};
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, JoinOnDsl, QueryDsl, QueryResult,
+    RunQueryDsl,
+};
 use serde_json::Value;
+use std::collections::HashMap;
 
 use crate::models::api_models::CrosswordMetadata;
-use crate::models::db_models::{Crossword, Solution, SolutionItem};
+use crate::models::db_models::{Crossword, MoveLogEntry, Solution, SolutionItem};
 use crate::models::errors::AppError;
 use crate::models::errors::AppError::InternalServerError;
+use crate::schema::crossword;
+use crate::schema::move_log;
 use crate::schema::solution::dsl::solution;
-use crate::schema::solution::{crossword_for, solution_json, team_for};
+use crate::schema::solution::{crossword_for, last_activity, seq, solution_json, team_for};
+use crate::services::solution_service::{merge_solution_items, to_move_log_entries, MoveKind};
 use crate::DbPool;
 
 pub async fn get_solution(
@@ -36,28 +43,156 @@ pub async fn get_solution(
     })
 }
 
-pub async fn store_or_update_solution(
+pub async fn get_solution_seq(
+    pool: web::Data<DbPool>,
+    crossword_id: String,
+    team_id: String,
+) -> actix_web::Result<i64, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    let result: Option<i64> = web::block(move || {
+        let mut conn = pool.get()?;
+        solution
+            .filter(crossword_for.eq(crossword_id))
+            .filter(team_for.eq(team_id))
+            .select(seq)
+            .first(&mut conn)
+            .optional()
+            .map_err(|x| AppError::InternalServerError(x.to_string()))
+    })
+    .await??;
+    Ok(result.unwrap_or(0))
+}
+
+/// Merges solution items into the stored solution inside a single transaction, locking the row so concurrent moves
+/// can't overwrite each other's cells, and logs what each move changed. Returns the items that were applied.
+pub async fn merge_into_solution(
     pool: web::Data<DbPool>,
     crossword_id: String,
     team_id: String,
     solution_items: Vec<SolutionItem>,
-) -> actix_web::Result<usize, AppError> {
-    // use web::block to offload blocking Diesel queries without blocking server thread1
+    kinds: HashMap<i64, MoveKind>,
+    seq_to_store: i64,
+) -> actix_web::Result<Vec<SolutionItem>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
     web::block(move || {
         let mut conn = pool.get()?;
-        let solution_json_to_insert = serde_json::to_value(solution_items)?;
-        let solution_to_insert = Solution {
-            team_for: team_id,
-            crossword_for: crossword_id,
-            solution_json: solution_json_to_insert.clone(),
-        };
-        diesel::insert_into(solution)
-            .values(&solution_to_insert)
-            .on_conflict((team_for, crossword_for))
-            .do_update()
-            .set((solution_json.eq(solution_json_to_insert.clone())))
-            .execute(&mut conn)
+        conn.transaction(|conn| {
+            // Make sure there is a row to lock before the first move on a crossword
+            diesel::insert_into(solution)
+                .values(&Solution {
+                    crossword_for: crossword_id.clone(),
+                    team_for: team_id.clone(),
+                    solution_json: Value::Array(Vec::new()),
+                    seq: 0,
+                    last_activity: 0,
+                })
+                .on_conflict((team_for, crossword_for))
+                .do_nothing()
+                .execute(conn)?;
+            let (current_json, current_seq): (Value, i64) = solution
+                .filter(crossword_for.eq(crossword_id.clone()))
+                .filter(team_for.eq(team_id.clone()))
+                .select((solution_json, seq))
+                .for_update()
+                .first(conn)?;
+            let current_items: Vec<SolutionItem> = serde_json::from_value(current_json)?;
+            let (merged_items, changes) = merge_solution_items(current_items, solution_items);
+            let log_entries =
+                to_move_log_entries(&crossword_id, &team_id, changes.clone(), &kinds)?;
+            diesel::insert_into(move_log::table)
+                .values(log_entries)
+                .execute(conn)?;
+            let merged_activity = merged_items
+                .iter()
+                .map(|item| item.updated_at)
+                .max()
+                .unwrap_or(0);
+            diesel::update(solution)
+                .filter(crossword_for.eq(crossword_id))
+                .filter(team_for.eq(team_id))
+                .set((
+                    solution_json.eq(serde_json::to_value(merged_items)?),
+                    seq.eq(current_seq.max(seq_to_store)),
+                    last_activity.eq(merged_activity),
+                ))
+                .execute(conn)?;
+            Ok(changes.into_iter().map(|(_, after)| after).collect())
+        })
+    })
+    .await?
+}
+
+/// Every logged move for a team's solve of a crossword, in the order they were persisted.
+pub async fn get_move_log(
+    pool: web::Data<DbPool>,
+    crossword_id: String,
+    team_id: String,
+) -> actix_web::Result<Vec<MoveLogEntry>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        move_log::table
+            .filter(move_log::crossword_for.eq(crossword_id))
+            .filter(move_log::team_for.eq(team_id))
+            .order(move_log::id.asc())
+            .load(&mut conn)
             .map_err(|e| AppError::InternalServerError(e.to_string()))
     })
     .await?
 }
+
+/// A page of the crosswords a team has a stored solution for, along with the crossword itself, most recently worked on
+/// first and starting after the solution with the last activity and crossword id `after`.
+pub async fn get_solutions_with_crosswords_for_team(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    after: Option<(i64, String)>,
+    limit: i64,
+) -> actix_web::Result<Vec<(Solution, Crossword)>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        let mut listing = solution
+            .inner_join(crossword::table.on(crossword::id.eq(crossword_for)))
+            .filter(team_for.eq(team_id))
+            .into_boxed();
+        if let Some((key, after_id)) = after {
+            listing = listing.filter(
+                last_activity
+                    .lt(key)
+                    .or(last_activity.eq(key).and(crossword_for.lt(after_id))),
+            );
+        }
+        listing
+            .order((last_activity.desc(), crossword_for.desc()))
+            .limit(limit)
+            .load(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// One user's latest `limit` logged moves for a team's solve of a crossword, in the order they were made.
+pub async fn get_user_move_log(
+    pool: web::Data<DbPool>,
+    crossword_id: String,
+    team_id: String,
+    user: String,
+    limit: i64,
+) -> actix_web::Result<Vec<MoveLogEntry>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        let mut entries: Vec<MoveLogEntry> = move_log::table
+            .filter(move_log::crossword_for.eq(crossword_id))
+            .filter(move_log::team_for.eq(team_id))
+            .filter(move_log::user_id.eq(user))
+            .order((move_log::seq.desc(), move_log::id.desc()))
+            .limit(limit)
+            .load(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+        entries.reverse();
+        Ok(entries)
+    })
+    .await?
+}
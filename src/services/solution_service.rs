@@ -1,83 +1,865 @@
 extern crate futures;
 extern crate serde;
 
-use crate::models::api_models::SolutionItemDto;
-use crate::models::db_models::SolutionItem;
+use crate::models::api_models::{
+    CellState, CrosswordProgressDto, ProgressPageDto, ProgressQuery, ProgressState, ReplayDto,
+    ReplayEditDto, SolutionItemDto,
+};
+use crate::models::db_models::{Crossword, MoveLogEntry, NewMoveLogEntry, Solution, SolutionItem};
 use crate::models::errors::AppError;
-use crate::services::solution_db_actions::{get_solution, store_or_update_solution};
+use crate::models::guardian::{Dimensions, GuardianCrossword};
+use crate::services::completion_db_actions::get_completions_for_team;
+use crate::services::crossword_service::{page_size, parse_cursor, white_cells};
+use crate::services::solution_db_actions::{
+    get_move_log, get_solution, get_solutions_with_crosswords_for_team, get_user_move_log,
+    merge_into_solution,
+};
+use crate::services::solution_writer::{Flush, SolutionWriter};
 use crate::DbPool;
+use actix::Addr;
 use actix_web::web;
+use chrono::Utc;
 use std::collections::HashMap;
 
-pub async fn update_solution(
-    pool: web::Data<DbPool>,
+/// A cell as it was before and after a write.
+pub type CellChange = (SolutionItem, SolutionItem);
+
+/// What made a move, recorded in the move log so that undo and redo can be worked out from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveKind {
+    /// Letters a user typed, which they can undo.
+    Move,
+    Reveal,
+    /// Reverts the logged move with the given `seq`.
+    Undo {
+        reverts: i64,
+    },
+    /// Reverts the logged undo with the given `seq`.
+    Redo {
+        reverts: i64,
+    },
+}
+
+impl MoveKind {
+    fn name(&self) -> &'static str {
+        match self {
+            MoveKind::Move => "move",
+            MoveKind::Reveal => "reveal",
+            MoveKind::Undo { .. } => "undo",
+            MoveKind::Redo { .. } => "redo",
+        }
+    }
+
+    fn reverts(&self) -> Option<i64> {
+        match self {
+            MoveKind::Move | MoveKind::Reveal => None,
+            MoveKind::Undo { reverts } | MoveKind::Redo { reverts } => Some(*reverts),
+        }
+    }
+}
+
+/// Stamps a move's items with the user and logical clock that decide which write of each cell wins.
+pub fn to_solution_items(
     solution_items_api: Vec<SolutionItemDto>,
-    user_id: String,
-    team_id: String,
-    crossword_id: String,
-) -> Result<String, AppError> {
-    let new_solution_items: Vec<SolutionItem> = solution_items_api
+    user_id: &str,
+    seq: i64,
+) -> Vec<SolutionItem> {
+    let updated_at = Utc::now().timestamp_millis();
+    solution_items_api
         .iter()
         .map(|solution_item| SolutionItem {
             x: solution_item.x,
             y: solution_item.y,
             value: solution_item.value.to_owned(),
-            modified_by: user_id.clone(),
+            state: solution_item.state,
+            candidates: solution_item.candidates.clone(),
+            modified_by: user_id.to_string(),
+            seq,
+            updated_at,
         })
-        .collect();
-    let current_solution_items = get_solution(pool.clone(), crossword_id.clone(), team_id.clone())
-        .await?
-        .unwrap_or(Vec::new());
+        .collect()
+}
 
+/// Checks that a move only writes white cells inside the grid with single letters, which are normalised to uppercase.
+/// Only the server reveals letters, so moves can't mark cells as revealed.
+pub fn validate_solution_items(
+    guardian_crossword: &GuardianCrossword,
+    solution_items: Vec<SolutionItemDto>,
+) -> Result<Vec<SolutionItemDto>, String> {
+    let white_cells = white_cells(guardian_crossword);
+    let Dimensions { cols, rows } = guardian_crossword.dimensions;
+    solution_items
+        .into_iter()
+        .map(|solution_item| {
+            let (x, y) = (solution_item.x, solution_item.y);
+            if !(0..cols).contains(&x) || !(0..rows).contains(&y) {
+                return Err(format!(
+                    "Cell ({}, {}) is outside the {}x{} grid",
+                    x, y, cols, rows
+                ));
+            }
+            if !white_cells.contains(&(x, y)) {
+                return Err(format!("Cell ({}, {}) is a black square", x, y));
+            }
+            if solution_item.state == CellState::Revealed {
+                return Err(format!(
+                    "Cell ({}, {}) can't be marked as revealed, use a reveal instead",
+                    x, y
+                ));
+            }
+            let value = normalise_letter(&solution_item.value).ok_or_else(|| {
+                format!(
+                    "Cell ({}, {}) can't hold {:?}, only a single letter",
+                    x, y, solution_item.value
+                )
+            })?;
+            let candidates = solution_item
+                .candidates
+                .iter()
+                .map(|candidate| {
+                    normalise_letter(candidate)
+                        .filter(|candidate| !candidate.is_empty())
+                        .ok_or_else(|| {
+                            format!(
+                                "Cell ({}, {}) can't have {:?} as a candidate, only a single letter",
+                                x, y, candidate
+                            )
+                        })
+                })
+                .collect::<Result<Vec<String>, String>>()?;
+            Ok(SolutionItemDto {
+                value,
+                candidates,
+                ..solution_item
+            })
+        })
+        .collect()
+}
+
+/// A single letter from A to Z in uppercase, or an empty string for a cleared cell. `None` for anything else.
+fn normalise_letter(value: &str) -> Option<String> {
+    let mut chars = value.trim().chars();
+    match (chars.next(), chars.next()) {
+        (None, _) => Some(String::new()),
+        (Some(letter), None) if letter.is_ascii_alphabetic() => {
+            Some(letter.to_ascii_uppercase().to_string())
+        }
+        _ => None,
+    }
+}
+
+pub async fn update_solution(
+    pool: web::Data<DbPool>,
+    solution_items: Vec<SolutionItem>,
+    kinds: HashMap<i64, MoveKind>,
+    team_id: String,
+    crossword_id: String,
+    seq: i64,
+) -> Result<Vec<SolutionItemDto>, AppError> {
+    let applied_items =
+        merge_into_solution(pool, crossword_id, team_id, solution_items, kinds, seq).await?;
+    Ok(applied_items
+        .into_iter()
+        .map(to_solution_item_dto)
+        .collect())
+}
+
+/// Merges new items into the current solution cell by cell, keeping the newest write of each cell.
+/// Returns the merged solution and each change the new items made to it, as the cell before and after.
+pub fn merge_solution_items(
+    current_solution_items: Vec<SolutionItem>,
+    new_solution_items: Vec<SolutionItem>,
+) -> (Vec<SolutionItem>, Vec<CellChange>) {
     let mut position_to_item: HashMap<(i64, i64), SolutionItem> = current_solution_items
         .into_iter()
         .map(|item| ((item.x, item.y), item))
         .collect();
+    let mut changes: Vec<CellChange> = Vec::new();
 
     for solution_item in new_solution_items {
         let position = (solution_item.x, solution_item.y);
         let existing_item = position_to_item.get(&position);
-        match existing_item {
-            None => {
-                position_to_item.insert(position, solution_item);
-            }
-            Some(item) => {
-                if item.value != solution_item.value {
-                    position_to_item.insert(position, solution_item);
-                }
-            }
+        let should_apply = match existing_item {
+            None => true,
+            Some(item) => !item.same_content(&solution_item) && solution_item.supersedes(item),
+        };
+        if should_apply {
+            let before = existing_item
+                .cloned()
+                .unwrap_or_else(|| SolutionItem::empty(solution_item.x, solution_item.y));
+            changes.push((before, solution_item.clone()));
+            position_to_item.insert(position, solution_item);
         }
     }
 
-    store_or_update_solution(
-        pool.clone(),
-        crossword_id.clone(),
-        team_id.clone(),
-        position_to_item.into_values().collect(),
-    )
-    .await?;
-    Ok("Success".to_string())
+    (position_to_item.into_values().collect(), changes)
+}
+
+/// Groups the changes a batch of moves made by the move that made them, as log entries for the room. `kinds` says
+/// what made each move by its `seq`, and moves missing from it are taken to be typed.
+pub fn to_move_log_entries(
+    crossword_id: &str,
+    team_id: &str,
+    changes: Vec<CellChange>,
+    kinds: &HashMap<i64, MoveKind>,
+) -> Result<Vec<NewMoveLogEntry>, AppError> {
+    let mut moves: Vec<((i64, String), Vec<CellChange>)> = Vec::new();
+    for (before, after) in changes {
+        let key = (after.seq, after.modified_by.clone());
+        match moves.iter_mut().find(|(move_key, _)| *move_key == key) {
+            Some((_, move_changes)) => move_changes.push((before, after)),
+            None => moves.push((key, vec![(before, after)])),
+        }
+    }
+    let created_at = Utc::now().timestamp_millis();
+    moves
+        .into_iter()
+        .map(|((seq, user_id), move_changes)| {
+            let (cells_before, cells_after): (Vec<SolutionItem>, Vec<SolutionItem>) =
+                move_changes.into_iter().unzip();
+            let kind = kinds.get(&seq).copied().unwrap_or(MoveKind::Move);
+            Ok(NewMoveLogEntry {
+                crossword_for: crossword_id.to_string(),
+                team_for: team_id.to_string(),
+                seq,
+                user_id,
+                cells_before: serde_json::to_value(cells_before)?,
+                cells_after: serde_json::to_value(cells_after)?,
+                created_at,
+                kind: kind.name().to_string(),
+                reverts: kind.reverts(),
+            })
+        })
+        .collect()
+}
+
+pub fn to_solution_item_dto(item: SolutionItem) -> SolutionItemDto {
+    SolutionItemDto {
+        x: item.x,
+        y: item.y,
+        value: item.value,
+        state: item.state,
+        candidates: item.candidates,
+        modified_by: item.modified_by,
+        seq: item.seq,
+        updated_at: item.updated_at,
+    }
 }
 
 pub async fn retrieve_and_send_solution(
     pool: web::Data<DbPool>,
     team_id: String,
     crossword_id: String,
-) -> Result<String, AppError> {
+) -> Result<Vec<SolutionItemDto>, AppError> {
     let solution_items = get_solution(pool, crossword_id, team_id)
         .await?
         .unwrap_or(Vec::new());
-    
-    // Convert SolutionItem to SolutionItemDto with modified_by field
-    let solution_items_dto: Vec<SolutionItemDto> = solution_items
+
+    Ok(solution_items
         .into_iter()
-        .map(|item| SolutionItemDto {
-            x: item.x,
-            y: item.y,
-            value: item.value,
-            modified_by: item.modified_by,
-        })
+        .map(to_solution_item_dto)
+        .collect())
+}
+
+/// How a replay's timeline is shortened.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReplayCompression {
+    /// Plays the solve this many times faster.
+    pub speed: Option<f64>,
+    /// Longest pause between moves, in milliseconds before `speed` is applied.
+    pub max_gap: Option<i64>,
+}
+
+/// Every move of a team's solve in the order it was made, timed for playback.
+pub async fn retrieve_replay(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+    compression: ReplayCompression,
+) -> Result<ReplayDto, AppError> {
+    let mut edits: Vec<ReplayEditDto> = Vec::new();
+    for entry in get_move_log(pool, crossword_id, team_id).await? {
+        let cells: Vec<SolutionItem> = serde_json::from_value(entry.cells_after)?;
+        // Cells carry the time the move was made, rather than when it was persisted
+        let made_at = cells
+            .first()
+            .map_or(entry.created_at, |cell| cell.updated_at);
+        edits.push(ReplayEditDto {
+            seq: entry.seq,
+            user: entry.user_id,
+            at: 0,
+            made_at,
+            cells: cells.into_iter().map(to_solution_item_dto).collect(),
+        });
+    }
+    Ok(to_replay(edits, compression))
+}
+
+/// Puts moves in the order they were made and times each for playback from the first, shortening pauses longer than
+/// `max_gap` and then speeding everything up by `speed`.
+fn to_replay(mut edits: Vec<ReplayEditDto>, compression: ReplayCompression) -> ReplayDto {
+    edits.sort_by_key(|edit| (edit.made_at, edit.seq));
+
+    let started_at = edits.first().map(|edit| edit.made_at);
+    let mut elapsed = 0;
+    let mut previous_made_at = started_at.unwrap_or_default();
+    for edit in edits.iter_mut() {
+        let gap = edit.made_at - previous_made_at;
+        elapsed += compression.max_gap.map_or(gap, |max_gap| gap.min(max_gap));
+        previous_made_at = edit.made_at;
+        edit.at = compression
+            .speed
+            .map_or(elapsed, |speed| (elapsed as f64 / speed).round() as i64);
+    }
+
+    ReplayDto {
+        started_at,
+        duration: edits.last().map_or(0, |edit| edit.at),
+        edits,
+    }
+}
+
+/// A page of the crosswords a team has started, with how much of each is filled in, most recently worked on first,
+/// and the cursor for the next page if there is one.
+pub async fn retrieve_progress(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    query: ProgressQuery,
+) -> Result<ProgressPageDto, AppError> {
+    let limit = page_size(query.limit)?;
+    let after = query.cursor.as_deref().map(parse_cursor).transpose()?;
+    // Ask for one more than the page holds to find out whether there is another page
+    let mut solutions =
+        get_solutions_with_crosswords_for_team(pool.clone(), team_id.clone(), after, limit + 1)
+            .await?;
+    let next_cursor = if solutions.len() as i64 > limit {
+        solutions.truncate(limit as usize);
+        solutions
+            .last()
+            .map(|(last, _)| format!("{}_{}", last.last_activity, last.crossword_for))
+    } else {
+        None
+    };
+    let completed_at: HashMap<String, i64> = get_completions_for_team(pool, team_id)
+        .await?
+        .into_iter()
+        .map(|completion| (completion.crossword_for, completion.completed_at))
         .collect();
-    
-    serde_json::to_string(&solution_items_dto).map_err(|e| AppError::InternalServerError(e.to_string()))
+    let progress = solutions
+        .into_iter()
+        .map(|(solution, crossword)| {
+            let completed_at = completed_at.get(&crossword.id).copied();
+            to_progress_dto(solution, crossword, completed_at)
+        })
+        .collect::<Result<Vec<CrosswordProgressDto>, AppError>>()?;
+    Ok(ProgressPageDto {
+        progress,
+        next_cursor,
+    })
+}
+
+fn to_progress_dto(
+    solution: Solution,
+    crossword: Crossword,
+    completed_at: Option<i64>,
+) -> Result<CrosswordProgressDto, AppError> {
+    let guardian_crossword: GuardianCrossword = serde_json::from_value(crossword.crossword_json)?;
+    let items: Vec<SolutionItem> = serde_json::from_value(solution.solution_json)?;
+    let cells = white_cells(&guardian_crossword);
+    let filled = items
+        .iter()
+        .filter(|item| !item.value.is_empty() && cells.contains(&(item.x, item.y)))
+        .count();
+    let percent_filled = if cells.is_empty() {
+        0
+    } else {
+        (filled * 100 / cells.len()) as i64
+    };
+    Ok(CrosswordProgressDto {
+        crossword_id: crossword.id,
+        series: crossword.series,
+        series_no: crossword.series_no,
+        date: crossword.date,
+        percent_filled,
+        state: if completed_at.is_some() {
+            ProgressState::Completed
+        } else {
+            ProgressState::InProgress
+        },
+        completed_at,
+        last_activity: items
+            .iter()
+            .map(|item| item.updated_at)
+            .max()
+            .filter(|updated_at| *updated_at > 0),
+    })
+}
+
+/// A user's moves that can still be undone and undos that can still be redone, latest last, worked out from their
+/// logged moves in a room in order. Typing a new move clears what can be redone, as in any editor.
+pub fn undo_stacks(entries: Vec<MoveLogEntry>) -> (Vec<MoveLogEntry>, Vec<MoveLogEntry>) {
+    let mut undo: Vec<MoveLogEntry> = Vec::new();
+    let mut redo: Vec<MoveLogEntry> = Vec::new();
+    for entry in entries {
+        match (entry.kind.as_str(), entry.reverts) {
+            ("move", _) => {
+                redo.clear();
+                undo.push(entry);
+            }
+            ("undo", Some(reverts)) => {
+                undo.retain(|undoable| undoable.seq != reverts);
+                redo.push(entry);
+            }
+            ("redo", Some(reverts)) => {
+                redo.retain(|redoable| redoable.seq != reverts);
+                undo.push(entry);
+            }
+            _ => {}
+        }
+    }
+    (undo, redo)
+}
+
+/// The cells a logged move changed, as they were before and after it.
+pub fn logged_changes(entry: &MoveLogEntry) -> Result<Vec<CellChange>, AppError> {
+    let cells_before: Vec<SolutionItem> = serde_json::from_value(entry.cells_before.clone())?;
+    let cells_after: Vec<SolutionItem> = serde_json::from_value(entry.cells_after.clone())?;
+    Ok(cells_before.into_iter().zip(cells_after).collect())
+}
+
+/// A user's latest `limit` logged moves in a room, in order, once this instance's pending writes for it have been
+/// persisted.
+pub async fn retrieve_user_move_log(
+    pool: web::Data<DbPool>,
+    writer: Addr<SolutionWriter>,
+    team_id: String,
+    crossword_id: String,
+    user: String,
+    limit: i64,
+) -> Result<Vec<MoveLogEntry>, AppError> {
+    writer
+        .send(Flush {
+            room: (team_id.clone(), crossword_id.clone()),
+        })
+        .await
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    get_user_move_log(pool, crossword_id, team_id, user, limit).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::guardian::{GuardianDirection, GuardianEntry, Position, SeparatorLocations};
+
+    fn item(x: i64, y: i64, value: &str, user: &str, seq: i64, updated_at: i64) -> SolutionItem {
+        SolutionItem {
+            x,
+            y,
+            value: value.to_string(),
+            modified_by: user.to_string(),
+            seq,
+            updated_at,
+            ..SolutionItem::default()
+        }
+    }
+
+    fn merged_cell(items: &[SolutionItem], x: i64, y: i64) -> &SolutionItem {
+        items
+            .iter()
+            .find(|item| (item.x, item.y) == (x, y))
+            .expect("cell should be in the merged solution")
+    }
+
+    #[test]
+    fn later_seq_supersedes_whatever_the_clock_says() {
+        let earlier = item(0, 0, "A", "bob", 1, 2_000);
+        let later = item(0, 0, "B", "alice", 2, 1_000);
+        assert!(later.supersedes(&earlier));
+        assert!(!earlier.supersedes(&later));
+    }
+
+    #[test]
+    fn equal_seqs_are_settled_by_time_then_user() {
+        let first = item(0, 0, "A", "bob", 3, 1_000);
+        let second = item(0, 0, "B", "alice", 3, 2_000);
+        assert!(second.supersedes(&first));
+
+        let alice = item(0, 0, "A", "alice", 3, 1_000);
+        let bob = item(0, 0, "B", "bob", 3, 1_000);
+        assert!(bob.supersedes(&alice));
+        assert!(!alice.supersedes(&bob));
+    }
+
+    #[test]
+    fn merge_applies_newer_writes_and_reports_what_they_replaced() {
+        let current = vec![
+            item(0, 0, "A", "bob", 1, 1_000),
+            item(1, 0, "T", "bob", 1, 1_000),
+        ];
+        let (merged, changes) =
+            merge_solution_items(current, vec![item(0, 0, "C", "alice", 2, 2_000)]);
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged_cell(&merged, 0, 0).value, "C");
+        assert_eq!(merged_cell(&merged, 1, 0).value, "T");
+        assert_eq!(changes.len(), 1);
+        let (before, after) = &changes[0];
+        assert_eq!(before.value, "A");
+        assert_eq!(after.value, "C");
+    }
+
+    #[test]
+    fn merge_keeps_newer_stored_cells_over_late_writes() {
+        let current = vec![item(0, 0, "C", "alice", 5, 2_000)];
+        let (merged, changes) =
+            merge_solution_items(current, vec![item(0, 0, "X", "bob", 4, 3_000)]);
+
+        assert_eq!(merged_cell(&merged, 0, 0).value, "C");
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn merge_ignores_writes_that_change_nothing() {
+        let current = vec![item(0, 0, "C", "alice", 1, 1_000)];
+        let (merged, changes) =
+            merge_solution_items(current, vec![item(0, 0, "C", "bob", 2, 2_000)]);
+
+        assert_eq!(merged_cell(&merged, 0, 0).modified_by, "alice");
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn merge_fills_new_cells_from_empty() {
+        let (merged, changes) =
+            merge_solution_items(Vec::new(), vec![item(2, 1, "O", "bob", 1, 1_000)]);
+
+        assert_eq!(merged_cell(&merged, 2, 1).value, "O");
+        assert_eq!(
+            changes,
+            vec![(SolutionItem::empty(2, 1), item(2, 1, "O", "bob", 1, 1_000))]
+        );
+    }
+
+    #[test]
+    fn merge_applies_writes_in_a_batch_in_order() {
+        let (merged, changes) = merge_solution_items(
+            Vec::new(),
+            vec![
+                item(0, 0, "A", "bob", 1, 1_000),
+                item(0, 0, "B", "bob", 2, 1_100),
+            ],
+        );
+
+        assert_eq!(merged_cell(&merged, 0, 0).value, "B");
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].0.value, "A");
+    }
+
+    fn entry(number: i64, direction: GuardianDirection, solution: &str) -> GuardianEntry {
+        GuardianEntry {
+            id: format!("{}-{:?}", number, direction),
+            number,
+            human_number: number.to_string(),
+            clue: "Clue".to_string(),
+            direction,
+            length: solution.len() as i64,
+            group: Vec::new(),
+            position: Position { x: 0, y: 0 },
+            separator_locations: SeparatorLocations { field: Vec::new() },
+            solution: Some(solution.to_string()),
+        }
+    }
+
+    /// A 3x3 grid whose only white cells are the top row and left column.
+    fn crossword() -> GuardianCrossword {
+        GuardianCrossword {
+            id: "quick/1".to_string(),
+            number: 1,
+            name: "Quick crossword No 1".to_string(),
+            creator: None,
+            date: 0,
+            web_publication_date: 0,
+            entries: vec![
+                entry(1, GuardianDirection::Across, "CAT"),
+                entry(1, GuardianDirection::Down, "CAB"),
+            ],
+            solution_available: true,
+            date_solution_available: 0,
+            dimensions: Dimensions { cols: 3, rows: 3 },
+            crossword_type: "quick".to_string(),
+            pdf: None,
+        }
+    }
+
+    fn dto(x: i64, y: i64, value: &str) -> SolutionItemDto {
+        SolutionItemDto {
+            x,
+            y,
+            value: value.to_string(),
+            ..SolutionItemDto::default()
+        }
+    }
+
+    #[test]
+    fn normalises_single_letters_to_uppercase() {
+        assert_eq!(normalise_letter("a"), Some("A".to_string()));
+        assert_eq!(normalise_letter(" Z "), Some("Z".to_string()));
+        assert_eq!(normalise_letter(""), Some(String::new()));
+        assert_eq!(normalise_letter("  "), Some(String::new()));
+    }
+
+    #[test]
+    fn refuses_anything_but_a_single_ascii_letter() {
+        assert_eq!(normalise_letter("ab"), None);
+        assert_eq!(normalise_letter("1"), None);
+        assert_eq!(normalise_letter("?"), None);
+        assert_eq!(normalise_letter("é"), None);
+        assert_eq!(normalise_letter("ß"), None);
+    }
+
+    #[test]
+    fn accepts_letters_in_white_cells() {
+        let mut pencilled = dto(0, 2, "b");
+        pencilled.state = CellState::Pencilled;
+        pencilled.candidates = vec!["b".to_string(), "p".to_string()];
+
+        let items =
+            validate_solution_items(&crossword(), vec![dto(2, 0, "t"), dto(1, 0, ""), pencilled])
+                .expect("move should be valid");
+
+        assert_eq!(items[0].value, "T");
+        assert_eq!(items[1].value, "");
+        assert_eq!(items[2].value, "B");
+        assert_eq!(items[2].state, CellState::Pencilled);
+        assert_eq!(items[2].candidates, vec!["B", "P"]);
+    }
+
+    #[test]
+    fn refuses_cells_off_the_grid_or_black() {
+        assert!(validate_solution_items(&crossword(), vec![dto(3, 0, "A")]).is_err());
+        assert!(validate_solution_items(&crossword(), vec![dto(0, -1, "A")]).is_err());
+        assert!(validate_solution_items(&crossword(), vec![dto(1, 1, "A")]).is_err());
+    }
+
+    #[test]
+    fn refuses_the_whole_move_for_one_bad_cell() {
+        let result = validate_solution_items(&crossword(), vec![dto(0, 0, "C"), dto(1, 0, "AB")]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn refuses_revealed_cells_and_empty_candidates() {
+        let mut revealed = dto(0, 0, "C");
+        revealed.state = CellState::Revealed;
+        assert!(validate_solution_items(&crossword(), vec![revealed]).is_err());
+
+        let mut empty_candidate = dto(0, 0, "");
+        empty_candidate.candidates = vec![String::new()];
+        assert!(validate_solution_items(&crossword(), vec![empty_candidate]).is_err());
+    }
+
+    fn replay_edit(seq: i64, made_at: i64) -> ReplayEditDto {
+        ReplayEditDto {
+            seq,
+            user: "bob".to_string(),
+            at: 0,
+            made_at,
+            cells: Vec::new(),
+        }
+    }
+
+    fn timeline(replay: &ReplayDto) -> Vec<(i64, i64)> {
+        replay
+            .edits
+            .iter()
+            .map(|edit| (edit.seq, edit.at))
+            .collect()
+    }
+
+    #[test]
+    fn replays_moves_in_the_order_they_were_made() {
+        let edits = vec![
+            replay_edit(2, 1_500),
+            replay_edit(1, 1_000),
+            replay_edit(3, 4_000),
+        ];
+        let replay = to_replay(edits, ReplayCompression::default());
+
+        assert_eq!(replay.started_at, Some(1_000));
+        assert_eq!(timeline(&replay), vec![(1, 0), (2, 500), (3, 3_000)]);
+        assert_eq!(replay.duration, 3_000);
+    }
+
+    #[test]
+    fn shortens_long_pauses_to_max_gap() {
+        let edits = vec![
+            replay_edit(1, 0),
+            replay_edit(2, 100),
+            replay_edit(3, 60_100),
+        ];
+        let compression = ReplayCompression {
+            max_gap: Some(1_000),
+            ..ReplayCompression::default()
+        };
+
+        assert_eq!(
+            timeline(&to_replay(edits, compression)),
+            vec![(1, 0), (2, 100), (3, 1_100)]
+        );
+    }
+
+    #[test]
+    fn speeds_up_after_shortening_pauses() {
+        let edits = vec![
+            replay_edit(1, 0),
+            replay_edit(2, 300),
+            replay_edit(3, 60_300),
+        ];
+        let compression = ReplayCompression {
+            speed: Some(2.0),
+            max_gap: Some(1_000),
+        };
+        let replay = to_replay(edits, compression);
+
+        assert_eq!(timeline(&replay), vec![(1, 0), (2, 150), (3, 650)]);
+        assert_eq!(replay.duration, 650);
+    }
+
+    #[test]
+    fn replays_nothing_for_an_unstarted_solve() {
+        let replay = to_replay(Vec::new(), ReplayCompression::default());
+        assert_eq!(replay.started_at, None);
+        assert_eq!(replay.duration, 0);
+        assert!(replay.edits.is_empty());
+    }
+
+    fn logged(seq: i64, kind: MoveKind) -> MoveLogEntry {
+        MoveLogEntry {
+            id: seq,
+            crossword_for: "quick/1".to_string(),
+            team_for: "team".to_string(),
+            seq,
+            user_id: "bob".to_string(),
+            cells_before: serde_json::json!([]),
+            cells_after: serde_json::json!([]),
+            created_at: 0,
+            kind: kind.name().to_string(),
+            reverts: kind.reverts(),
+        }
+    }
+
+    fn stack_seqs(entries: Vec<MoveLogEntry>) -> (Vec<i64>, Vec<i64>) {
+        let (undo, redo) = undo_stacks(entries);
+        (
+            undo.iter().map(|entry| entry.seq).collect(),
+            redo.iter().map(|entry| entry.seq).collect(),
+        )
+    }
+
+    #[test]
+    fn typed_moves_can_be_undone_but_reveals_cannot() {
+        let entries = vec![
+            logged(1, MoveKind::Move),
+            logged(2, MoveKind::Reveal),
+            logged(3, MoveKind::Move),
+        ];
+        assert_eq!(stack_seqs(entries), (vec![1, 3], vec![]));
+    }
+
+    #[test]
+    fn undos_can_be_redone_and_redos_undone_again() {
+        let entries = vec![
+            logged(1, MoveKind::Move),
+            logged(2, MoveKind::Move),
+            logged(3, MoveKind::Undo { reverts: 2 }),
+            logged(4, MoveKind::Undo { reverts: 1 }),
+        ];
+        assert_eq!(stack_seqs(entries.clone()), (vec![], vec![3, 4]));
+
+        let mut redone = entries;
+        redone.push(logged(5, MoveKind::Redo { reverts: 4 }));
+        assert_eq!(stack_seqs(redone), (vec![5], vec![3]));
+    }
+
+    #[test]
+    fn a_new_move_clears_what_can_be_redone() {
+        let entries = vec![
+            logged(1, MoveKind::Move),
+            logged(2, MoveKind::Undo { reverts: 1 }),
+            logged(3, MoveKind::Move),
+        ];
+        assert_eq!(stack_seqs(entries), (vec![3], vec![]));
+    }
+
+    fn progress(
+        crossword: GuardianCrossword,
+        items: Vec<SolutionItem>,
+        completed_at: Option<i64>,
+    ) -> CrosswordProgressDto {
+        let stored_crossword = Crossword {
+            id: crossword.id.clone(),
+            series: "quick".to_string(),
+            series_no: crossword.number,
+            date: crossword.date,
+            crossword_json: serde_json::to_value(&crossword).expect("should serialize"),
+        };
+        let solution = Solution {
+            crossword_for: crossword.id,
+            team_for: "team".to_string(),
+            solution_json: serde_json::to_value(items).expect("should serialize"),
+            seq: 0,
+            last_activity: 0,
+        };
+        to_progress_dto(solution, stored_crossword, completed_at).expect("should be readable")
+    }
+
+    #[test]
+    fn progress_only_counts_letters_in_white_cells() {
+        let items = vec![
+            item(0, 0, "C", "bob", 1, 1_000),
+            item(1, 0, "A", "bob", 2, 2_000),
+            item(2, 0, "", "bob", 3, 3_000),
+            item(1, 1, "X", "bob", 4, 4_000),
+        ];
+        // 2 of the 5 white cells are filled in
+        assert_eq!(progress(crossword(), items, None).percent_filled, 40);
+    }
+
+    #[test]
+    fn progress_of_a_grid_without_white_cells_is_zero() {
+        let empty = GuardianCrossword {
+            entries: Vec::new(),
+            ..crossword()
+        };
+        let items = vec![item(0, 0, "C", "bob", 1, 1_000)];
+        assert_eq!(progress(empty, items, None).percent_filled, 0);
+    }
+
+    #[test]
+    fn untouched_solutions_have_no_last_activity() {
+        assert_eq!(progress(crossword(), Vec::new(), None).last_activity, None);
+        // Cells written before they were timestamped
+        let items = vec![item(0, 0, "C", "bob", 1, 0)];
+        assert_eq!(progress(crossword(), items, None).last_activity, None);
+
+        let items = vec![
+            item(0, 0, "C", "bob", 1, 1_000),
+            item(1, 0, "A", "bob", 2, 2_000),
+        ];
+        assert_eq!(
+            progress(crossword(), items, None).last_activity,
+            Some(2_000)
+        );
+    }
+
+    #[test]
+    fn progress_is_completed_once_there_is_a_completion() {
+        let in_progress = progress(crossword(), Vec::new(), None);
+        assert_eq!(in_progress.state, ProgressState::InProgress);
+        assert_eq!(in_progress.completed_at, None);
+
+        let completed = progress(crossword(), Vec::new(), Some(5_000));
+        assert_eq!(completed.state, ProgressState::Completed);
+        assert_eq!(completed.completed_at, Some(5_000));
+    }
 }
@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use crate::models::api_models::TimerDto;
+use crate::models::db_models::SolutionItem;
+use crate::models::ws_messages::ServerMessage;
+use crate::services::solution_service::{update_solution, MoveKind};
+use crate::services::timer_service::save_timer;
+use crate::services::ws_server::RoomKey;
+use crate::services::ws_session;
+use crate::services::ws_session::WsSession;
+use crate::DbPool;
+use actix::prelude::*;
+use actix_web::web::Data;
+use tokio::sync::oneshot;
+
+/// How long to wait for further keystrokes in a room before persisting them together.
+const WRITE_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Session to acknowledge once a move has been persisted.
+#[derive(Debug, Clone)]
+pub struct AckTarget {
+    pub addr: Addr<WsSession>,
+    pub client_move_id: Option<String>,
+    pub seq: i64,
+}
+
+/// Queues a sequenced move to be merged into the room's stored solution.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QueueWrite {
+    pub room: RoomKey,
+    pub seq: i64,
+    pub solution_items: Vec<SolutionItem>,
+    pub kind: MoveKind,
+    pub ack: Option<AckTarget>,
+    /// Room to tell if the move can't be persisted, having already broadcast it.
+    pub on_failure: Recipient<WriteFailed>,
+}
+
+/// Queues a room's timer to be saved, replacing any earlier timer for the room that hasn't started saving yet.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QueueTimer {
+    pub room: RoomKey,
+    pub timer: TimerDto,
+}
+
+/// Moves a room broadcast could not be persisted, so its solution no longer matches the stored one.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct WriteFailed;
+
+/// Persists anything queued for a room, resolving once none of its writes are still in flight.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Flush {
+    pub room: RoomKey,
+}
+
+#[derive(Default)]
+struct PendingWrite {
+    seq: i64,
+    solution_items: Vec<SolutionItem>,
+    kinds: HashMap<i64, MoveKind>,
+    acks: Vec<AckTarget>,
+    on_failure: Option<Recipient<WriteFailed>>,
+}
+
+/// Batches moves per room and persists them off the `MoveServer`, so broadcasting never waits on the database. Also
+/// saves rooms' timers, one at a time per room so they land in order.
+pub struct SolutionWriter {
+    pool: DbPool,
+    pending: HashMap<RoomKey, PendingWrite>,
+    /// Latest timer of each room that is waiting for the previous one to be saved.
+    pending_timers: HashMap<RoomKey, TimerDto>,
+    /// Rooms with a timer being saved.
+    saving_timers: HashSet<RoomKey>,
+    in_flight: HashMap<RoomKey, usize>,
+    flush_waiters: HashMap<RoomKey, Vec<oneshot::Sender<()>>>,
+}
+
+impl SolutionWriter {
+    pub fn new(pool: DbPool) -> SolutionWriter {
+        SolutionWriter {
+            pool,
+            pending: HashMap::new(),
+            pending_timers: HashMap::new(),
+            saving_timers: HashSet::new(),
+            in_flight: HashMap::new(),
+            flush_waiters: HashMap::new(),
+        }
+    }
+
+    fn write(&mut self, room: RoomKey, ctx: &mut Context<Self>) {
+        let Some(PendingWrite {
+            seq,
+            solution_items,
+            kinds,
+            acks,
+            on_failure,
+        }) = self.pending.remove(&room)
+        else {
+            return;
+        };
+        *self.in_flight.entry(room.clone()).or_default() += 1;
+
+        let (team, crossword) = room.clone();
+        let write = update_solution(
+            Data::new(self.pool.clone()),
+            solution_items,
+            kinds,
+            team,
+            crossword,
+            seq,
+        )
+        .into_actor(self)
+        .map(move |result, act, _| {
+            for ack in acks {
+                let message = match &result {
+                    Ok(applied_items) => ServerMessage::Ack {
+                        client_move_id: ack.client_move_id,
+                        seq: ack.seq,
+                        // Every move in a batch has its own seq
+                        items: applied_items
+                            .iter()
+                            .filter(|item| item.seq == ack.seq)
+                            .cloned()
+                            .collect(),
+                    },
+                    Err(e) => ServerMessage::Rejected {
+                        client_move_id: ack.client_move_id,
+                        message: e.to_string(),
+                    },
+                };
+                ack.addr.do_send(ws_session::Message(message));
+            }
+            if let Err(e) = result {
+                println!("Error persisting moves for {:?}: {}", room, e);
+                if let Some(on_failure) = on_failure {
+                    on_failure.do_send(WriteFailed);
+                }
+            }
+            act.write_finished(&room);
+        });
+        ctx.spawn(write);
+    }
+
+    fn save_next_timer(&mut self, room: RoomKey, ctx: &mut Context<Self>) {
+        if self.saving_timers.contains(&room) {
+            return;
+        }
+        let Some(timer) = self.pending_timers.remove(&room) else {
+            return;
+        };
+        self.saving_timers.insert(room.clone());
+        *self.in_flight.entry(room.clone()).or_default() += 1;
+
+        let (team, crossword) = room.clone();
+        let save = save_timer(Data::new(self.pool.clone()), team, crossword, timer)
+            .into_actor(self)
+            .map(move |result, act, ctx| {
+                if let Err(e) = result {
+                    println!("Error saving timer for {:?}: {}", room, e);
+                }
+                act.saving_timers.remove(&room);
+                // Started before this save counts as finished, so a flush waits for it too
+                act.save_next_timer(room.clone(), ctx);
+                act.write_finished(&room);
+            });
+        ctx.spawn(save);
+    }
+
+    fn write_finished(&mut self, room: &RoomKey) {
+        let in_flight = self.in_flight.entry(room.clone()).or_default();
+        *in_flight = in_flight.saturating_sub(1);
+        if *in_flight == 0 {
+            self.in_flight.remove(room);
+            for waiter in self.flush_waiters.remove(room).unwrap_or_default() {
+                let _ = waiter.send(());
+            }
+        }
+    }
+}
+
+impl Actor for SolutionWriter {
+    type Context = Context<Self>;
+}
+
+impl Handler<QueueWrite> for SolutionWriter {
+    type Result = ();
+
+    fn handle(&mut self, msg: QueueWrite, ctx: &mut Context<Self>) {
+        let is_first_in_batch = !self.pending.contains_key(&msg.room);
+        let pending = self.pending.entry(msg.room.clone()).or_default();
+        pending.seq = pending.seq.max(msg.seq);
+        pending.solution_items.extend(msg.solution_items);
+        pending.kinds.insert(msg.seq, msg.kind);
+        pending.acks.extend(msg.ack);
+        pending.on_failure = Some(msg.on_failure);
+
+        if is_first_in_batch {
+            ctx.run_later(WRITE_DEBOUNCE, move |act, ctx| act.write(msg.room, ctx));
+        }
+    }
+}
+
+impl Handler<QueueTimer> for SolutionWriter {
+    type Result = ();
+
+    fn handle(&mut self, msg: QueueTimer, ctx: &mut Context<Self>) {
+        self.pending_timers.insert(msg.room.clone(), msg.timer);
+        self.save_next_timer(msg.room, ctx);
+    }
+}
+
+impl Handler<Flush> for SolutionWriter {
+    type Result = ResponseFuture<()>;
+
+    fn handle(&mut self, msg: Flush, ctx: &mut Context<Self>) -> Self::Result {
+        self.write(msg.room.clone(), ctx);
+        if !self.in_flight.contains_key(&msg.room) {
+            return Box::pin(async {});
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        self.flush_waiters.entry(msg.room).or_default().push(sender);
+        Box::pin(async move {
+            let _ = receiver.await;
+        })
+    }
+}
@@ -0,0 +1,289 @@
+use actix_web::web;
+use diesel::{Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+
+use crate::models::db_models::{Team, TeamMembership, TeamSettings};
+use crate::models::errors::AppError;
+use crate::schema::team_settings::dsl::{team_id, team_settings};
+use crate::schema::{account, team, team_membership};
+use crate::DbPool;
+
+pub async fn get_team_settings(
+    pool: web::Data<DbPool>,
+    team_for: String,
+) -> actix_web::Result<Option<TeamSettings>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        team_settings
+            .filter(team_id.eq(team_for))
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+pub async fn store_team_settings(
+    pool: web::Data<DbPool>,
+    settings: TeamSettings,
+) -> actix_web::Result<usize, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        diesel::insert_into(team_settings)
+            .values(&settings)
+            .on_conflict(team_id)
+            .do_update()
+            .set(&settings)
+            .execute(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// Creates a team along with its first membership, its owner's.
+pub async fn store_team(
+    pool: web::Data<DbPool>,
+    new_team: Team,
+    owner: TeamMembership,
+) -> actix_web::Result<(), AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        conn.transaction(|conn| {
+            diesel::insert_into(team::table)
+                .values(new_team)
+                .execute(conn)?;
+            diesel::insert_into(team_membership::table)
+                .values(owner)
+                .execute(conn)?;
+            Ok(())
+        })
+        .map_err(|e: diesel::result::Error| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+pub async fn get_team(
+    pool: web::Data<DbPool>,
+    team_for: String,
+) -> actix_web::Result<Option<Team>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        team::table
+            .filter(team::id.eq(team_for))
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+pub async fn get_team_by_invite_code(
+    pool: web::Data<DbPool>,
+    code: String,
+) -> actix_web::Result<Option<Team>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        team::table
+            .filter(team::invite_code.eq(code))
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+pub async fn update_invite_code(
+    pool: web::Data<DbPool>,
+    team_for: String,
+    code: String,
+) -> actix_web::Result<usize, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        diesel::update(team::table.filter(team::id.eq(team_for)))
+            .set(team::invite_code.eq(code))
+            .execute(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// Every team a user belongs to, along with their membership of it.
+pub async fn get_teams_for_user(
+    pool: web::Data<DbPool>,
+    user: String,
+) -> actix_web::Result<Vec<(Team, TeamMembership)>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        team::table
+            .inner_join(team_membership::table)
+            .filter(team_membership::username.eq(user))
+            .order(team::name.asc())
+            .load(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+pub async fn get_membership(
+    pool: web::Data<DbPool>,
+    team_for: String,
+    user: String,
+) -> actix_web::Result<Option<TeamMembership>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        team_membership::table
+            .filter(team_membership::team_id.eq(team_for))
+            .filter(team_membership::username.eq(user))
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// A team's memberships with each member's display name, longest-standing first.
+pub async fn get_members(
+    pool: web::Data<DbPool>,
+    team_for: String,
+) -> actix_web::Result<Vec<(TeamMembership, String)>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        team_membership::table
+            .inner_join(account::table)
+            .filter(team_membership::team_id.eq(team_for))
+            .order(team_membership::joined_at.asc())
+            .select((team_membership::all_columns, account::display_name))
+            .load(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// Adds a membership unless the user already belongs to the team. A team with no members yet is claimed by the
+/// first user to join, who is given `owner_role` instead, with the team locked so only one user can claim it.
+pub async fn store_membership(
+    pool: web::Data<DbPool>,
+    membership: TeamMembership,
+    owner_role: String,
+) -> actix_web::Result<usize, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        conn.transaction(|conn| {
+            team::table
+                .filter(team::id.eq(membership.team_id.clone()))
+                .select(team::id)
+                .for_update()
+                .first::<String>(conn)?;
+            let members: i64 = team_membership::table
+                .filter(team_membership::team_id.eq(membership.team_id.clone()))
+                .count()
+                .get_result(conn)?;
+            let membership = claiming_if_empty(membership, members, owner_role);
+            diesel::insert_into(team_membership::table)
+                .values(membership)
+                .on_conflict((team_membership::team_id, team_membership::username))
+                .do_nothing()
+                .execute(conn)
+        })
+        .map_err(|e: diesel::result::Error| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+/// The membership to store for a user joining a team that already has `members` members, which makes the first to
+/// join its owner.
+fn claiming_if_empty(
+    membership: TeamMembership,
+    members: i64,
+    owner_role: String,
+) -> TeamMembership {
+    if members == 0 {
+        TeamMembership {
+            role: owner_role,
+            ..membership
+        }
+    } else {
+        membership
+    }
+}
+
+/// Sets the roles of several members at once, so ownership can be handed over in one go.
+pub async fn update_roles(
+    pool: web::Data<DbPool>,
+    team_for: String,
+    roles: Vec<(String, String)>,
+) -> actix_web::Result<(), AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        conn.transaction(|conn| {
+            for (user, new_role) in roles {
+                diesel::update(
+                    team_membership::table
+                        .filter(team_membership::team_id.eq(team_for.clone()))
+                        .filter(team_membership::username.eq(user)),
+                )
+                .set(team_membership::role.eq(new_role))
+                .execute(conn)?;
+            }
+            Ok(())
+        })
+        .map_err(|e: diesel::result::Error| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+pub async fn delete_membership(
+    pool: web::Data<DbPool>,
+    team_for: String,
+    user: String,
+) -> actix_web::Result<usize, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        diesel::delete(
+            team_membership::table
+                .filter(team_membership::team_id.eq(team_for))
+                .filter(team_membership::username.eq(user)),
+        )
+        .execute(&mut conn)
+        .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn joining(role: &str) -> TeamMembership {
+        TeamMembership {
+            team_id: "team".to_string(),
+            username: "bob".to_string(),
+            role: role.to_string(),
+            joined_at: 0,
+        }
+    }
+
+    #[test]
+    fn first_to_join_a_team_with_no_members_becomes_its_owner() {
+        let membership = claiming_if_empty(joining("member"), 0, "owner".to_string());
+        assert_eq!(membership.role, "owner");
+        assert_eq!(membership.username, "bob");
+    }
+
+    #[test]
+    fn later_joiners_keep_the_role_they_joined_with() {
+        let membership = claiming_if_empty(joining("member"), 1, "owner".to_string());
+        assert_eq!(membership.role, "member");
+    }
+}
@@ -0,0 +1,384 @@
+use crate::models::api_models::{
+    RevealPolicy, TeamDto, TeamMemberDto, TeamRole, TeamSettingsDto, TeamSummaryDto,
+};
+use crate::models::db_models::{Team, TeamMembership, TeamSettings};
+use crate::models::errors::AppError;
+use crate::services::team_db_actions::{
+    delete_membership, get_members, get_membership, get_team, get_team_by_invite_code,
+    get_team_settings, get_teams_for_user, store_membership, store_team, store_team_settings,
+    update_invite_code, update_roles,
+};
+use crate::services::util::validate_text;
+use crate::DbPool;
+use actix_web::web;
+use chrono::Utc;
+use uuid::Uuid;
+
+const MAX_TEAM_NAME_LENGTH: usize = 50;
+
+/// A team's settings, falling back to the defaults for teams that haven't changed them.
+pub async fn retrieve_team_settings(
+    pool: web::Data<DbPool>,
+    team_id: String,
+) -> Result<TeamSettingsDto, AppError> {
+    let Some(settings) = get_team_settings(pool, team_id).await? else {
+        return Ok(TeamSettingsDto::default());
+    };
+    let reveal_policy = match settings.reveal_policy.as_str() {
+        "auto" => RevealPolicy::Auto,
+        "vote" => RevealPolicy::Vote,
+        other => {
+            return Err(AppError::InternalServerError(format!(
+                "Unknown reveal policy: {}",
+                other
+            )))
+        }
+    };
+    Ok(TeamSettingsDto { reveal_policy })
+}
+
+pub async fn update_team_settings(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    settings: TeamSettingsDto,
+) -> Result<TeamSettingsDto, AppError> {
+    let reveal_policy = match settings.reveal_policy {
+        RevealPolicy::Auto => "auto",
+        RevealPolicy::Vote => "vote",
+    };
+    store_team_settings(
+        pool,
+        TeamSettings {
+            team_id,
+            reveal_policy: reveal_policy.to_string(),
+        },
+    )
+    .await?;
+    Ok(settings)
+}
+
+/// The user's role in a team, as long as the team exists and they have at least the role `required`.
+pub async fn require_role(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    user: &str,
+    required: TeamRole,
+) -> Result<TeamRole, AppError> {
+    let Some(membership) = get_membership(pool.clone(), team_id.clone(), user.to_string()).await?
+    else {
+        if get_team(pool, team_id.clone()).await?.is_none() {
+            return Err(AppError::TeamNotFound(team_id));
+        }
+        return Err(AppError::Forbidden(format!(
+            "You aren't a member of team {}",
+            team_id
+        )));
+    };
+    let role = parse_role(&membership.role)?;
+    check_role(role, required)?;
+    Ok(role)
+}
+
+/// Whether a member with `role` may do something that needs `required`, which any higher role may also do.
+fn check_role(role: TeamRole, required: TeamRole) -> Result<(), AppError> {
+    if role < required {
+        return Err(AppError::Forbidden(format!(
+            "You need to be a team {:?} or above to do that",
+            required
+        )));
+    }
+    Ok(())
+}
+
+/// Creates a team owned by the user who created it.
+pub async fn create_team(
+    pool: web::Data<DbPool>,
+    name: String,
+    owner: String,
+) -> Result<TeamDto, AppError> {
+    let name =
+        validate_text(&name, MAX_TEAM_NAME_LENGTH, "Team name").map_err(AppError::BadRequest)?;
+    let now = Utc::now().timestamp_millis();
+    let new_team = Team {
+        id: Uuid::new_v4().to_string(),
+        name,
+        invite_code: new_invite_code(),
+        created_at: now,
+    };
+    let membership = TeamMembership {
+        team_id: new_team.id.clone(),
+        username: owner.clone(),
+        role: role_to_string(TeamRole::Owner),
+        joined_at: now,
+    };
+    let team_id = new_team.id.clone();
+    store_team(pool.clone(), new_team, membership).await?;
+    retrieve_team(pool, team_id, owner).await
+}
+
+/// A team and its members, as seen by one of them.
+pub async fn retrieve_team(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    caller: String,
+) -> Result<TeamDto, AppError> {
+    let role = require_role(pool.clone(), team_id.clone(), &caller, TeamRole::Member).await?;
+    let team = get_team(pool.clone(), team_id.clone())
+        .await?
+        .ok_or_else(|| AppError::TeamNotFound(team_id.clone()))?;
+    let members = get_members(pool, team_id)
+        .await?
+        .into_iter()
+        .map(|(membership, name)| {
+            Ok(TeamMemberDto {
+                user: membership.username,
+                name,
+                role: parse_role(&membership.role)?,
+                joined_at: membership.joined_at,
+            })
+        })
+        .collect::<Result<Vec<TeamMemberDto>, AppError>>()?;
+    Ok(TeamDto {
+        id: team.id,
+        name: team.name,
+        role,
+        invite_code: (role >= TeamRole::Admin).then_some(team.invite_code),
+        members,
+    })
+}
+
+pub async fn retrieve_teams(
+    pool: web::Data<DbPool>,
+    user: String,
+) -> Result<Vec<TeamSummaryDto>, AppError> {
+    get_teams_for_user(pool, user)
+        .await?
+        .into_iter()
+        .map(|(team, membership)| {
+            Ok(TeamSummaryDto {
+                id: team.id,
+                name: team.name,
+                role: parse_role(&membership.role)?,
+            })
+        })
+        .collect()
+}
+
+/// Adds the user to the team an invite code belongs to, as a member unless they already belong to it. The first user
+/// to join a team with no members, such as one carried over from before teams had members, becomes its owner.
+pub async fn join_team(
+    pool: web::Data<DbPool>,
+    invite_code: String,
+    user: String,
+) -> Result<TeamDto, AppError> {
+    let team = get_team_by_invite_code(pool.clone(), invite_code)
+        .await?
+        .ok_or_else(|| AppError::BadRequest("That invite code isn't valid".to_string()))?;
+    store_membership(
+        pool.clone(),
+        TeamMembership {
+            team_id: team.id.clone(),
+            username: user.clone(),
+            role: role_to_string(TeamRole::Member),
+            joined_at: Utc::now().timestamp_millis(),
+        },
+        role_to_string(TeamRole::Owner),
+    )
+    .await?;
+    retrieve_team(pool, team.id, user).await
+}
+
+/// Replaces a team's invite code, so the old one stops working.
+pub async fn reset_invite_code(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    caller: String,
+) -> Result<TeamDto, AppError> {
+    require_role(pool.clone(), team_id.clone(), &caller, TeamRole::Admin).await?;
+    update_invite_code(pool.clone(), team_id.clone(), new_invite_code()).await?;
+    retrieve_team(pool, team_id, caller).await
+}
+
+/// Lets the owner change a member's role. Making someone else the owner hands ownership over, leaving the old
+/// owner an admin.
+pub async fn change_role(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    caller: String,
+    user: String,
+    role: TeamRole,
+) -> Result<TeamDto, AppError> {
+    require_role(pool.clone(), team_id.clone(), &caller, TeamRole::Owner).await?;
+    let roles = role_changes(&caller, &user, role)?;
+    if get_membership(pool.clone(), team_id.clone(), user.clone())
+        .await?
+        .is_none()
+    {
+        return Err(AppError::BadRequest(format!(
+            "{} isn't a member of this team",
+            user
+        )));
+    }
+    update_roles(pool.clone(), team_id.clone(), roles).await?;
+    retrieve_team(pool, team_id, caller).await
+}
+
+/// The roles the owner giving `user` a new role leaves each member with, handing ownership over if it is given away.
+fn role_changes(
+    caller: &str,
+    user: &str,
+    role: TeamRole,
+) -> Result<Vec<(String, String)>, AppError> {
+    if user == caller {
+        return Err(AppError::BadRequest(
+            "Make someone else the owner to give up ownership".to_string(),
+        ));
+    }
+    let mut roles = vec![(user.to_string(), role_to_string(role))];
+    if role == TeamRole::Owner {
+        roles.push((caller.to_string(), role_to_string(TeamRole::Admin)));
+    }
+    Ok(roles)
+}
+
+/// Takes a user out of a team. Anyone but the owner can leave, admins can remove members and the owner can
+/// remove anyone else.
+pub async fn remove_member(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    caller: String,
+    user: String,
+) -> Result<(), AppError> {
+    let caller_role =
+        require_role(pool.clone(), team_id.clone(), &caller, TeamRole::Member).await?;
+    let membership = get_membership(pool.clone(), team_id.clone(), user.clone())
+        .await?
+        .ok_or_else(|| AppError::BadRequest(format!("{} isn't a member of this team", user)))?;
+    check_removal(&caller, caller_role, &user, parse_role(&membership.role)?)?;
+    delete_membership(pool, team_id, user).await?;
+    Ok(())
+}
+
+/// Whether a member with `caller_role` may take `user`, whose role is `role`, out of the team.
+fn check_removal(
+    caller: &str,
+    caller_role: TeamRole,
+    user: &str,
+    role: TeamRole,
+) -> Result<(), AppError> {
+    if role == TeamRole::Owner {
+        return Err(AppError::BadRequest(
+            "The owner can't leave, make someone else the owner first".to_string(),
+        ));
+    }
+    if user != caller && caller_role <= role {
+        return Err(AppError::Forbidden(format!(
+            "You can't remove a team {:?}",
+            role
+        )));
+    }
+    Ok(())
+}
+
+fn new_invite_code() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+fn parse_role(role: &str) -> Result<TeamRole, AppError> {
+    match role {
+        "owner" => Ok(TeamRole::Owner),
+        "admin" => Ok(TeamRole::Admin),
+        "member" => Ok(TeamRole::Member),
+        other => Err(AppError::InternalServerError(format!(
+            "Unknown team role: {}",
+            other
+        ))),
+    }
+}
+
+fn role_to_string(role: TeamRole) -> String {
+    match role {
+        TeamRole::Owner => "owner",
+        TeamRole::Admin => "admin",
+        TeamRole::Member => "member",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn higher_roles_can_do_what_lower_roles_can() {
+        assert!(check_role(TeamRole::Member, TeamRole::Member).is_ok());
+        assert!(check_role(TeamRole::Admin, TeamRole::Member).is_ok());
+        assert!(check_role(TeamRole::Owner, TeamRole::Admin).is_ok());
+        assert!(matches!(
+            check_role(TeamRole::Member, TeamRole::Admin),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            check_role(TeamRole::Admin, TeamRole::Owner),
+            Err(AppError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn members_and_admins_can_leave() {
+        assert!(check_removal("bob", TeamRole::Member, "bob", TeamRole::Member).is_ok());
+        assert!(check_removal("bob", TeamRole::Admin, "bob", TeamRole::Admin).is_ok());
+    }
+
+    #[test]
+    fn the_owner_cannot_leave_or_be_removed() {
+        assert!(matches!(
+            check_removal("alice", TeamRole::Owner, "alice", TeamRole::Owner),
+            Err(AppError::BadRequest(_))
+        ));
+        assert!(matches!(
+            check_removal("bob", TeamRole::Admin, "alice", TeamRole::Owner),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn only_higher_roles_can_remove_someone_else() {
+        assert!(check_removal("alice", TeamRole::Owner, "bob", TeamRole::Admin).is_ok());
+        assert!(check_removal("alice", TeamRole::Admin, "bob", TeamRole::Member).is_ok());
+        assert!(matches!(
+            check_removal("alice", TeamRole::Admin, "bob", TeamRole::Admin),
+            Err(AppError::Forbidden(_))
+        ));
+        assert!(matches!(
+            check_removal("alice", TeamRole::Member, "bob", TeamRole::Member),
+            Err(AppError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn giving_away_ownership_leaves_the_old_owner_an_admin() {
+        let roles = role_changes("alice", "bob", TeamRole::Owner).expect("should hand over");
+        assert_eq!(
+            roles,
+            vec![
+                ("bob".to_string(), "owner".to_string()),
+                ("alice".to_string(), "admin".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn other_role_changes_only_change_that_member() {
+        let roles = role_changes("alice", "bob", TeamRole::Admin).expect("should change role");
+        assert_eq!(roles, vec![("bob".to_string(), "admin".to_string())]);
+    }
+
+    #[test]
+    fn the_owner_cannot_change_their_own_role() {
+        assert!(matches!(
+            role_changes("alice", "alice", TeamRole::Admin),
+            Err(AppError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,43 @@
+use actix_web::web;
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+
+use crate::models::db_models::SolveTimer;
+use crate::models::errors::AppError;
+use crate::schema::solve_timer::dsl::{crossword_for, solve_timer, team_for};
+use crate::DbPool;
+
+pub async fn get_solve_timer(
+    pool: web::Data<DbPool>,
+    crossword_id: String,
+    team_id: String,
+) -> actix_web::Result<Option<SolveTimer>, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        solve_timer
+            .filter(crossword_for.eq(crossword_id))
+            .filter(team_for.eq(team_id))
+            .first(&mut conn)
+            .optional()
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
+
+pub async fn store_solve_timer(
+    pool: web::Data<DbPool>,
+    timer: SolveTimer,
+) -> actix_web::Result<usize, AppError> {
+    // use web::block to offload blocking Diesel queries without blocking server thread
+    web::block(move || {
+        let mut conn = pool.get()?;
+        diesel::insert_into(solve_timer)
+            .values(&timer)
+            .on_conflict((crossword_for, team_for))
+            .do_update()
+            .set(&timer)
+            .execute(&mut conn)
+            .map_err(|e| AppError::InternalServerError(e.to_string()))
+    })
+    .await?
+}
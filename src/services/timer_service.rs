@@ -0,0 +1,70 @@
+use crate::models::api_models::TimerDto;
+use crate::models::db_models::SolveTimer;
+use crate::models::errors::AppError;
+use crate::services::timer_db_actions::{get_solve_timer, store_solve_timer};
+use crate::DbPool;
+use actix_web::web;
+use chrono::Utc;
+
+/// Milliseconds on the clock at server time `now`.
+pub fn elapsed_at(timer: &TimerDto, now: i64) -> i64 {
+    if timer.running {
+        timer.elapsed + (now - timer.at).max(0)
+    } else {
+        timer.elapsed
+    }
+}
+
+/// The timer started or stopped at server time `now`, keeping the time already on the clock.
+pub fn with_running(timer: &TimerDto, running: bool, now: i64) -> TimerDto {
+    TimerDto {
+        elapsed: elapsed_at(timer, now),
+        at: now,
+        running,
+        ..timer.clone()
+    }
+}
+
+/// A team's timer for a crossword, stopped at zero if nobody has made a move yet.
+pub async fn retrieve_timer(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+) -> Result<TimerDto, AppError> {
+    let now = Utc::now().timestamp_millis();
+    let Some(timer) = get_solve_timer(pool, crossword_id, team_id).await? else {
+        return Ok(TimerDto {
+            at: now,
+            ..TimerDto::default()
+        });
+    };
+    Ok(TimerDto {
+        started_at: timer.started_at,
+        elapsed: timer.elapsed,
+        at: timer.running_since.unwrap_or(now),
+        running: timer.running_since.is_some(),
+        paused: timer.paused,
+    })
+}
+
+pub async fn save_timer(
+    pool: web::Data<DbPool>,
+    team_id: String,
+    crossword_id: String,
+    timer: TimerDto,
+) -> Result<(), AppError> {
+    store_solve_timer(
+        pool,
+        SolveTimer {
+            crossword_for: crossword_id,
+            team_for: team_id,
+            started_at: timer.started_at,
+            elapsed: timer.elapsed,
+            // A running timer keeps counting from when it was last started, wherever it is loaded
+            running_since: timer.running.then_some(timer.at),
+            paused: timer.paused,
+        },
+    )
+    .await?;
+    Ok(())
+}
@@ -1,4 +1,21 @@
 use chrono::{DateTime, Datelike, Month};
+
+/// Trims text sent by a user, rejecting it if nothing is left or it is longer than `max_length` characters.
+pub fn validate_text(text: &str, max_length: usize, kind: &str) -> Result<String, String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(format!("{} is empty", kind));
+    }
+    let length = text.chars().count();
+    if length > max_length {
+        return Err(format!(
+            "{} is {} characters long, the limit is {}",
+            kind, length, max_length
+        ));
+    }
+    Ok(text.to_string())
+}
+
 pub fn to_human_readable_date(date: i64) -> String {
     let datetime = DateTime::from_timestamp(date / 1000, 0).unwrap();
 
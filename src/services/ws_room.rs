@@ -0,0 +1,1732 @@
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::models::api_models::{
+    AssistTarget, CellPositionDto, CellState, ChatMessageDto, CompletionDto, CurrentCellDto,
+    Direction, PresenceDto, RevealPolicy, RevealProposalDto, SolutionItemDto, TimerDto,
+};
+use crate::models::db_models::MoveLogEntry;
+use crate::models::errors::AppError;
+use crate::models::guardian::GuardianCrossword;
+use crate::models::ws_messages::{ErrorCode, SequencedMove, ServerMessage};
+use crate::services::assist_service::{check, log_assist, target_letters};
+use crate::services::chat_service::{get_chat_history, send_chat_message, validate_chat_text};
+use crate::services::comment_service::{add_clue_comment, validate_comment_text};
+use crate::services::completion_service::{is_complete, record_completion, retrieve_completion};
+use crate::services::crossword_db_actions::get_crossword_by_id;
+use crate::services::crossword_service::{
+    entry_covering, find_entry, solutions_published, white_cells,
+};
+use crate::services::fanout::{Fanout, Publish, RemoteEvent, RoomEvent};
+use crate::services::solution_db_actions::get_solution_seq;
+use crate::services::solution_service::{
+    logged_changes, retrieve_and_send_solution, retrieve_user_move_log, to_solution_item_dto,
+    to_solution_items, undo_stacks, validate_solution_items, MoveKind,
+};
+use crate::services::solution_writer::{
+    AckTarget, Flush, QueueTimer, QueueWrite, SolutionWriter, WriteFailed,
+};
+use crate::services::team_service::retrieve_team_settings;
+use crate::services::timer_service::{retrieve_timer, with_running};
+use crate::services::ws_server::RoomKey;
+use crate::services::ws_session;
+use crate::services::ws_session::WsSession;
+use crate::DbPool;
+use actix::prelude::*;
+use actix_web::web::Data;
+use chrono::Utc;
+use uuid::Uuid;
+
+/// Session joins the room
+#[derive(Message, Debug, Clone)]
+#[rtype(ServerMessage)]
+pub struct Join {
+    pub session: WsSession,
+    pub addr: Addr<WsSession>,
+}
+
+/// Session leaves the room
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Leave {
+    pub id: Uuid,
+}
+
+/// Last member has left, so the room stops once everything before this has been handled
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Close;
+
+/// User has been removed from the team, so their sessions are closed
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RemoveMember {
+    pub user: String,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Move {
+    pub client_move_id: Option<String>,
+    pub solution_items: Vec<SolutionItemDto>,
+    pub sender: WsSession,
+}
+
+/// Reverts the sender's user's latest move that hasn't been undone.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Undo {
+    pub client_move_id: Option<String>,
+    pub sender: WsSession,
+}
+
+/// Reapplies the sender's user's latest undone move.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Redo {
+    pub client_move_id: Option<String>,
+    pub sender: WsSession,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CurrentCell {
+    pub x: i64,
+    pub y: i64,
+    pub direction: Option<Direction>,
+    pub clue: Option<i64>,
+    pub sender: WsSession,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Chat {
+    pub text: String,
+    pub sender: WsSession,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Check {
+    pub target: AssistTarget,
+    pub sender: WsSession,
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Comment {
+    pub clue: i64,
+    pub direction: Direction,
+    pub text: String,
+    pub sender: WsSession,
+}
+
+/// Proposes revealing part of the grid, which may need the rest of the room to agree.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Reveal {
+    pub target: AssistTarget,
+    pub sender: WsSession,
+}
+
+/// Accepts or rejects a reveal proposed by someone else.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RevealVote {
+    pub id: Uuid,
+    pub accept: bool,
+    pub sender: WsSession,
+}
+
+/// Pauses or resumes the room's timer.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PauseTimer {
+    pub paused: bool,
+}
+
+/// Number of recent moves kept per room for clients resuming after a reconnect.
+const RESUMABLE_MOVES: usize = 500;
+
+/// Number of each user's latest logged moves, undos and redos among them, that undo and redo look back through.
+const UNDOABLE_MOVES: i64 = 100;
+
+/// Number of recent chat messages sent to sessions when they connect.
+const CHAT_HISTORY: usize = 50;
+
+/// How long a reveal proposal waits for votes before lapsing.
+const REVEAL_VOTE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often the room looks for cursors that have gone idle.
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often the room tells other instances who is connected to it here.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long another instance can go unheard before its users are taken out of the room, as if it had crashed.
+const REMOTE_PRESENCE_TIMEOUT: Duration = Duration::from_secs(45);
+
+#[derive(Clone, Debug, Default)]
+struct RoomLog {
+    seq: i64,
+    recent_moves: VecDeque<SequencedMove>,
+    /// Moves from other instances that arrived after later sequence numbers had already been sent,
+    /// as the late move's `seq` and the room's `seq` when it arrived.
+    late_moves: VecDeque<(i64, i64)>,
+}
+
+impl RoomLog {
+    fn record(&mut self, seq: i64, items: Vec<SolutionItemDto>) -> SequencedMove {
+        let sequenced_move = SequencedMove { seq, items };
+        self.seq = seq;
+        self.push(sequenced_move.clone());
+        sequenced_move
+    }
+
+    /// Records a move sequenced by another instance, moving this room's clock past it.
+    fn record_remote(&mut self, sequenced_move: SequencedMove) {
+        if sequenced_move.seq <= self.seq {
+            self.late_moves.push_back((sequenced_move.seq, self.seq));
+            if self.late_moves.len() > RESUMABLE_MOVES {
+                self.late_moves.pop_front();
+            }
+        } else {
+            self.seq = sequenced_move.seq;
+        }
+        self.push(sequenced_move);
+    }
+
+    fn push(&mut self, sequenced_move: SequencedMove) {
+        self.recent_moves.push_back(sequenced_move);
+        if self.recent_moves.len() > RESUMABLE_MOVES {
+            self.recent_moves.pop_front();
+        }
+    }
+
+    /// Moves after `last_seq`, or `None` if some of them are no longer in the log.
+    fn moves_since(&self, last_seq: i64) -> Option<Vec<SequencedMove>> {
+        let oldest_seq = self
+            .recent_moves
+            .front()
+            .map_or(self.seq + 1, |sequenced_move| sequenced_move.seq);
+        // Clients that had already seen a later move when a late one arrived can't tell they missed it
+        let may_have_missed_late_move = self
+            .late_moves
+            .iter()
+            .any(|(late_seq, seen_seq)| *late_seq <= last_seq && last_seq <= *seen_seq);
+        if last_seq > self.seq || last_seq + 1 < oldest_seq || may_have_missed_late_move {
+            return None;
+        }
+        Some(
+            self.recent_moves
+                .iter()
+                .filter(|sequenced_move| sequenced_move.seq > last_seq)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// Cells changed by a move, as they were before and after it.
+type Edit = Vec<(SolutionItemDto, SolutionItemDto)>;
+
+/// Undo or redo waiting for the ones requested before it in the room.
+struct UndoRequest {
+    sender: WsSession,
+    client_move_id: Option<String>,
+    redo: bool,
+}
+
+struct RevealProposal {
+    dto: RevealProposalDto,
+    /// Session that proposed the reveal if that was on this instance, in which case this room counts the votes.
+    proposer: Option<Uuid>,
+}
+
+/// Live state of one team solving one crossword: its members, their cursors and the solution so far.
+pub struct Room {
+    key: RoomKey,
+    members: HashMap<Uuid, Join>,
+    /// Presence of users connected to this room through other instances, by user and instance.
+    remote_presence: HashMap<String, HashMap<Uuid, PresenceDto>>,
+    /// When each other instance serving this room was last heard from.
+    remote_heard_at: HashMap<Uuid, Instant>,
+    /// Latest cursor of each user, wherever they are connected.
+    cursors: HashMap<String, CurrentCellDto>,
+    /// When each user's cursor last moved, as seen by this instance.
+    cursor_moved_at: HashMap<String, Instant>,
+    /// How long a cursor can stay put before it is shown as idle.
+    cursor_idle_after: Duration,
+    solution: HashMap<(i64, i64), SolutionItemDto>,
+    /// Crossword being solved, or `None` if it isn't stored.
+    crossword: Option<GuardianCrossword>,
+    /// Set once the team has finished the crossword.
+    completion: Option<CompletionDto>,
+    /// Whether a completion is being recorded.
+    recording_completion: bool,
+    timer: TimerDto,
+    log: RoomLog,
+    /// Open reveal proposals, wherever they were made.
+    reveal_proposals: HashMap<Uuid, RevealProposal>,
+    /// Undos and redos waiting their turn, oldest first.
+    undo_requests: VecDeque<UndoRequest>,
+    /// Whether an undo or redo is reading the move log.
+    undoing: bool,
+    /// Most recent chat messages, oldest first.
+    chat_history: VecDeque<ChatMessageDto>,
+    load_error: Option<AppError>,
+    pool: DbPool,
+    writer: Addr<SolutionWriter>,
+    fanout: Addr<Fanout>,
+    /// Previous room for the same team and crossword, which may still be handing its moves to the writer.
+    predecessor: Option<Addr<Room>>,
+}
+
+impl Room {
+    pub fn new(
+        key: RoomKey,
+        pool: DbPool,
+        writer: Addr<SolutionWriter>,
+        fanout: Addr<Fanout>,
+        predecessor: Option<Addr<Room>>,
+        cursor_idle_after: Duration,
+    ) -> Room {
+        Room {
+            key,
+            members: HashMap::new(),
+            remote_presence: HashMap::new(),
+            remote_heard_at: HashMap::new(),
+            cursors: HashMap::new(),
+            cursor_moved_at: HashMap::new(),
+            cursor_idle_after,
+            solution: HashMap::new(),
+            crossword: None,
+            completion: None,
+            recording_completion: false,
+            timer: TimerDto::default(),
+            log: RoomLog::default(),
+            reveal_proposals: HashMap::new(),
+            undo_requests: VecDeque::new(),
+            undoing: false,
+            chat_history: VecDeque::new(),
+            load_error: None,
+            pool,
+            writer,
+            fanout,
+            predecessor,
+        }
+    }
+
+    /// Applies cells to the in-memory solution, keeping the newest write of each cell, and returns the changes made.
+    fn apply_to_solution(&mut self, items: &[SolutionItemDto]) -> Edit {
+        let mut edit = Vec::new();
+        for item in items {
+            let existing = self.solution.get(&(item.x, item.y));
+            let should_apply = match existing {
+                None => true,
+                Some(existing) => {
+                    (&existing.value, existing.state, &existing.candidates)
+                        != (&item.value, item.state, &item.candidates)
+                        && (item.seq, item.updated_at, &item.modified_by)
+                            >= (existing.seq, existing.updated_at, &existing.modified_by)
+                }
+            };
+            if should_apply {
+                let before = existing.cloned().unwrap_or_else(|| SolutionItemDto {
+                    x: item.x,
+                    y: item.y,
+                    ..SolutionItemDto::default()
+                });
+                self.solution.insert((item.x, item.y), item.clone());
+                edit.push((before, item.clone()));
+            }
+        }
+        edit
+    }
+
+    /// Sequences cells written by a session, broadcasts them straight away and queues them to be persisted. Only
+    /// plain moves skip the sender, who has already drawn them.
+    fn make_move(
+        &mut self,
+        user: &str,
+        sender_id: Uuid,
+        client_move_id: Option<String>,
+        items: Vec<SolutionItemDto>,
+        kind: MoveKind,
+        ctx: &mut Context<Self>,
+    ) {
+        if let Some(e) = &self.load_error {
+            self.send_to(
+                &sender_id,
+                ServerMessage::Rejected {
+                    client_move_id,
+                    message: e.to_string(),
+                },
+            );
+            return;
+        }
+
+        let seq = self.log.seq + 1;
+        let solution_items = to_solution_items(items, user, seq);
+        let item_dtos: Vec<SolutionItemDto> = solution_items
+            .iter()
+            .cloned()
+            .map(to_solution_item_dto)
+            .collect();
+        self.apply_to_solution(&item_dtos);
+        let sequenced_move = self.log.record(seq, item_dtos);
+        self.publish(RoomEvent::Move(sequenced_move.clone()));
+        if !matches!(kind, MoveKind::Move) {
+            self.broadcast(ServerMessage::Move(sequenced_move));
+        } else {
+            self.broadcast_moves(&sender_id, sequenced_move);
+        }
+
+        let ack = self.members.get(&sender_id).map(|member| AckTarget {
+            addr: member.addr.clone(),
+            client_move_id,
+            seq,
+        });
+        self.writer.do_send(QueueWrite {
+            room: self.key.clone(),
+            seq,
+            solution_items,
+            kind,
+            ack,
+            on_failure: ctx.address().recipient(),
+        });
+        if self.timer.started_at.is_none() {
+            self.set_timer(TimerDto {
+                started_at: Some(Utc::now().timestamp_millis()),
+                ..self.timer.clone()
+            });
+        }
+        self.detect_completion(ctx);
+    }
+
+    /// The cells an earlier edit replaced, as they were, leaving out any that now hold something else. Cells put back
+    /// as they were by a later undo still count, so that moves can be undone one after another.
+    fn reverted_cells(&self, edit: Edit) -> Vec<SolutionItemDto> {
+        edit.into_iter()
+            .rev()
+            .filter(|(_, after)| {
+                self.solution
+                    .get(&(after.x, after.y))
+                    .is_some_and(|current| {
+                        (&current.value, current.state, &current.candidates)
+                            == (&after.value, after.state, &after.candidates)
+                    })
+            })
+            .map(|(before, _)| before)
+            .collect()
+    }
+
+    /// Reverts the sender's latest move, or with `redo` their latest undo, that still has cells to put back. Both are
+    /// worked out from the move log, so they survive the room closing and users moving between instances.
+    fn undo_or_redo(
+        &mut self,
+        sender: WsSession,
+        client_move_id: Option<String>,
+        redo: bool,
+        ctx: &mut Context<Self>,
+    ) {
+        self.undo_requests.push_back(UndoRequest {
+            sender,
+            client_move_id,
+            redo,
+        });
+        self.next_undo(ctx);
+    }
+
+    /// Handles the oldest waiting undo or redo, unless one is already reading the move log. The room carries on with
+    /// other messages meanwhile, and only works out which cells to revert once the log has been read. One at a time,
+    /// so each reads the log with the one before it in.
+    fn next_undo(&mut self, ctx: &mut Context<Self>) {
+        if self.undoing {
+            return;
+        }
+        let Some(UndoRequest {
+            sender,
+            client_move_id,
+            redo,
+        }) = self.undo_requests.pop_front()
+        else {
+            return;
+        };
+        self.undoing = true;
+        let (team, crossword) = self.key.clone();
+        retrieve_user_move_log(
+            Data::new(self.pool.clone()),
+            self.writer.clone(),
+            team,
+            crossword,
+            sender.user.clone(),
+            UNDOABLE_MOVES,
+        )
+        .into_actor(self)
+        .map(move |result, act, ctx| {
+            act.undoing = false;
+            act.undo_from_log(result, sender, client_move_id, redo, ctx);
+            act.next_undo(ctx);
+        })
+        .spawn(ctx);
+    }
+
+    /// Reverts the latest of the sender's logged moves, or with `redo` their undos, that still has cells to put back.
+    fn undo_from_log(
+        &mut self,
+        result: Result<Vec<MoveLogEntry>, AppError>,
+        sender: WsSession,
+        client_move_id: Option<String>,
+        redo: bool,
+        ctx: &mut Context<Self>,
+    ) {
+        let entries = match result {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.send_to(
+                    &sender.id,
+                    ServerMessage::error(ErrorCode::InternalError, e.to_string()),
+                );
+                return;
+            }
+        };
+        let (undoable, redoable) = undo_stacks(entries);
+        let candidates = if redo { redoable } else { undoable };
+        let target = candidates.iter().rev().find_map(|entry| {
+            let edit: Edit = logged_changes(entry)
+                .ok()?
+                .into_iter()
+                .map(|(before, after)| (to_solution_item_dto(before), to_solution_item_dto(after)))
+                .collect();
+            let items = self.reverted_cells(edit);
+            (!items.is_empty()).then_some((entry.seq, items))
+        });
+        let Some((reverts, items)) = target else {
+            let message = if redo {
+                "Nothing to redo"
+            } else {
+                "Nothing to undo"
+            };
+            self.send_to(
+                &sender.id,
+                ServerMessage::error(ErrorCode::InvalidMessage, message.to_string()),
+            );
+            return;
+        };
+        let kind = if redo {
+            MoveKind::Redo { reverts }
+        } else {
+            MoveKind::Undo { reverts }
+        };
+        self.make_move(&sender.user, sender.id, client_move_id, items, kind, ctx);
+    }
+
+    /// A user's presence across their sessions on this instance, if they have any.
+    fn local_presence(&self, user: &str) -> Option<PresenceDto> {
+        self.members
+            .values()
+            .map(|member| &member.session)
+            .filter(|ws_session| ws_session.user == user)
+            .min_by_key(|ws_session| ws_session.connected_at)
+            .map(|ws_session| PresenceDto {
+                user: ws_session.user.clone(),
+                name: ws_session.name.clone(),
+                current_cell: self.cursors.get(user).cloned(),
+                connected_since: ws_session.connected_at,
+            })
+    }
+
+    /// A user's presence across all their sessions on every instance, if they have any.
+    fn presence(&self, user: &str) -> Option<PresenceDto> {
+        self.local_presence(user)
+            .into_iter()
+            .chain(
+                self.remote_presence
+                    .get(user)
+                    .into_iter()
+                    .flat_map(|by_instance| by_instance.values().cloned()),
+            )
+            .min_by_key(|presence| presence.connected_since)
+            .map(|presence| PresenceDto {
+                current_cell: self.cursors.get(user).cloned(),
+                ..presence
+            })
+    }
+
+    fn roster(&self) -> Vec<PresenceDto> {
+        let users: BTreeSet<&String> = self
+            .members
+            .values()
+            .map(|member| &member.session.user)
+            .chain(self.remote_presence.keys())
+            .collect();
+        users
+            .into_iter()
+            .filter_map(|user| self.presence(user))
+            .collect()
+    }
+
+    /// Tells everyone a user has gone once none of their sessions are left on any instance.
+    fn user_left(&mut self, user: &str, ctx: &mut Context<Self>) {
+        if self.presence(user).is_none() {
+            self.reveal_voter_left(user, ctx);
+            self.cursor_moved_at.remove(user);
+            if self.cursors.remove(user).is_some() {
+                self.broadcast(ServerMessage::CursorRemoved {
+                    user: user.to_string(),
+                });
+            }
+            self.broadcast(ServerMessage::Leave {
+                user: user.to_string(),
+            });
+        }
+    }
+
+    /// Takes the users another instance no longer has in the room out of it, for when it has missed saying they left.
+    fn forget_remote_users(
+        &mut self,
+        instance: Uuid,
+        still_present: impl Fn(&str) -> bool,
+        ctx: &mut Context<Self>,
+    ) {
+        let gone: Vec<String> = self
+            .remote_presence
+            .iter()
+            .filter(|(user, by_instance)| {
+                by_instance.contains_key(&instance) && !still_present(user)
+            })
+            .map(|(user, _)| user.clone())
+            .collect();
+        if gone.is_empty() {
+            return;
+        }
+        for user in gone {
+            if let Some(by_instance) = self.remote_presence.get_mut(&user) {
+                by_instance.remove(&instance);
+                if by_instance.is_empty() {
+                    self.remote_presence.remove(&user);
+                }
+            }
+            self.user_left(&user, ctx);
+        }
+        self.set_timer(self.timer.clone());
+    }
+
+    /// Tells other instances who is here, and forgets the users of instances that haven't been heard from for too long.
+    fn heartbeat(&mut self, ctx: &mut Context<Self>) {
+        let users: BTreeSet<String> = self
+            .members
+            .values()
+            .map(|member| member.session.user.clone())
+            .collect();
+        if !users.is_empty() {
+            self.publish(RoomEvent::Heartbeat {
+                users: users.into_iter().collect(),
+            });
+        }
+        let silent: Vec<Uuid> = self
+            .remote_heard_at
+            .iter()
+            .filter(|(_, heard_at)| heard_at.elapsed() > REMOTE_PRESENCE_TIMEOUT)
+            .map(|(instance, _)| *instance)
+            .collect();
+        for instance in silent {
+            println!("Instance {} went quiet in room {:?}", instance, self.key);
+            self.remote_heard_at.remove(&instance);
+            self.forget_remote_users(instance, |_| false, ctx);
+        }
+    }
+
+    fn publish(&self, event: RoomEvent) {
+        self.fanout.do_send(Publish {
+            room: self.key.clone(),
+            event,
+        });
+    }
+
+    /// Sends a message to every member of the room, including the sender.
+    fn broadcast(&self, message: ServerMessage) {
+        for member in self.members.values() {
+            member.addr.do_send(ws_session::Message(message.clone()));
+        }
+    }
+
+    fn broadcast_to_others(&self, sender: &WsSession, message: ServerMessage) {
+        for member in self.members.values() {
+            if member.session.id != sender.id {
+                member.addr.do_send(ws_session::Message(message.clone()));
+            }
+        }
+    }
+
+    fn send_to(&self, id: &Uuid, message: ServerMessage) {
+        if let Some(member) = self.members.get(id) {
+            member.addr.do_send(ws_session::Message(message));
+        }
+    }
+
+    fn broadcast_moves(&self, sender_id: &Uuid, sequenced_move: SequencedMove) {
+        // The sender learns the sequence number and stored cells from its ack instead
+        let message = ServerMessage::Move(sequenced_move);
+        for member in self.members.values() {
+            if member.session.id != *sender_id {
+                member.addr.do_send(ws_session::Message(message.clone()));
+            }
+        }
+    }
+
+    /// Number of the clue a cursor is on, checked against the crossword's entries.
+    fn clue_at(
+        &self,
+        x: i64,
+        y: i64,
+        direction: Option<&Direction>,
+        clue: Option<i64>,
+    ) -> Result<Option<i64>, String> {
+        let Some(direction) = direction else {
+            return match clue {
+                None => Ok(None),
+                Some(_) => Err("A clue needs a direction".to_string()),
+            };
+        };
+        let crossword = self
+            .crossword
+            .as_ref()
+            .ok_or_else(|| format!("Could not find crossword for id: {}", self.key.1))?;
+        entry_covering(crossword, x, y, direction, clue)
+            .map(|entry| Some(entry.number))
+            .ok_or_else(|| match clue {
+                Some(clue) => format!("{:?} clue {} doesn't cover ({}, {})", direction, clue, x, y),
+                None => format!("No {:?} clue covers ({}, {})", direction, x, y),
+            })
+    }
+
+    fn broadcast_current_cell(&self, current_cell: CurrentCellDto) {
+        self.publish(RoomEvent::Cursor(current_cell.clone()));
+        self.broadcast(ServerMessage::Cursor(current_cell));
+    }
+
+    /// Records a user's latest cursor, which is no longer idle now that it has moved.
+    fn move_cursor(&mut self, current_cell: CurrentCellDto) {
+        self.cursor_moved_at
+            .insert(current_cell.user.clone(), Instant::now());
+        self.cursors.insert(
+            current_cell.user.clone(),
+            CurrentCellDto {
+                idle: false,
+                ..current_cell
+            },
+        );
+    }
+
+    /// Tells everyone about cursors that haven't moved for `cursor_idle_after`, once each.
+    fn mark_idle_cursors(&mut self) {
+        let now = Instant::now();
+        let mut idle_cursors = Vec::new();
+        for (user, current_cell) in self.cursors.iter_mut() {
+            let moved_at = self.cursor_moved_at.get(user).copied().unwrap_or(now);
+            if !current_cell.idle && now.duration_since(moved_at) >= self.cursor_idle_after {
+                current_cell.idle = true;
+                idle_cursors.push(current_cell.clone());
+            }
+        }
+        for current_cell in idle_cursors {
+            self.broadcast(ServerMessage::Cursor(current_cell));
+        }
+    }
+
+    fn broadcast_all_current_positions(&self, new_session: &WsSession) {
+        // Collect all current positions from other users in the room, wherever they are connected
+        let all_positions: Vec<CurrentCellDto> = self
+            .cursors
+            .values()
+            .filter(|current_cell| current_cell.user != new_session.user)
+            .cloned()
+            .collect();
+
+        // Send all current positions to the new client
+        for position in all_positions {
+            self.send_to(&new_session.id, ServerMessage::Cursor(position));
+        }
+    }
+
+    /// Keeps a chat message for sessions that connect later, unless it is already held.
+    fn record_chat(&mut self, chat_message: &ChatMessageDto) -> bool {
+        if self
+            .chat_history
+            .iter()
+            .any(|existing| existing.id == chat_message.id)
+        {
+            return false;
+        }
+        self.chat_history.push_back(chat_message.clone());
+        if self.chat_history.len() > CHAT_HISTORY {
+            self.chat_history.pop_front();
+        }
+        true
+    }
+
+    /// The room's crossword, as long as the Guardian has published its solutions, which checks and reveals need.
+    fn published_crossword(&self) -> Result<&GuardianCrossword, String> {
+        let crossword = self
+            .crossword
+            .as_ref()
+            .ok_or_else(|| format!("Could not find crossword for id: {}", self.key.1))?;
+        if !solutions_published(crossword) {
+            return Err("Solutions for this crossword haven't been published yet".to_string());
+        }
+        Ok(crossword)
+    }
+
+    /// Solution letters for a reveal.
+    fn reveal_letters(
+        &self,
+        target: &AssistTarget,
+    ) -> Result<Vec<(CellPositionDto, String)>, String> {
+        target_letters(self.published_crossword()?, target)
+    }
+
+    /// Reveals straight away if the team allows it or nobody else is here, otherwise asks everyone else to vote.
+    fn propose_reveal(
+        &mut self,
+        target: AssistTarget,
+        sender: WsSession,
+        reveal_policy: RevealPolicy,
+        ctx: &mut Context<Self>,
+    ) {
+        let voters: Vec<String> = self
+            .roster()
+            .into_iter()
+            .map(|presence| presence.user)
+            .filter(|user| *user != sender.user)
+            .collect();
+        if reveal_policy == RevealPolicy::Auto || voters.is_empty() {
+            self.reveal(target, &sender.user, sender.id, ctx);
+            return;
+        }
+
+        let id = Uuid::new_v4();
+        let expires_at = Utc::now().timestamp_millis() + REVEAL_VOTE_TIMEOUT.as_millis() as i64;
+        let dto = RevealProposalDto {
+            id,
+            target,
+            proposed_by: sender.user,
+            voters,
+            accepted_by: Vec::new(),
+            expires_at,
+        };
+        self.reveal_proposals.insert(
+            id,
+            RevealProposal {
+                dto: dto.clone(),
+                proposer: Some(sender.id),
+            },
+        );
+        self.publish(RoomEvent::RevealProposal(dto.clone()));
+        self.broadcast(ServerMessage::RevealProposal(dto));
+        ctx.run_later(REVEAL_VOTE_TIMEOUT, move |act, ctx| {
+            act.resolve_reveal(id, false, ctx);
+        });
+    }
+
+    /// Counts a vote on a reveal proposed on this instance.
+    fn count_reveal_vote(
+        &mut self,
+        id: Uuid,
+        user: &str,
+        accept: bool,
+        ctx: &mut Context<Self>,
+    ) -> Result<(), String> {
+        let Some(proposal) = self.reveal_proposals.get_mut(&id) else {
+            return Err("No such reveal proposal".to_string());
+        };
+        if !proposal.dto.voters.iter().any(|voter| voter == user) {
+            return Err("You aren't voting on this reveal".to_string());
+        }
+        if !accept {
+            self.resolve_reveal(id, false, ctx);
+            return Ok(());
+        }
+        if !proposal.dto.accepted_by.iter().any(|voter| voter == user) {
+            proposal.dto.accepted_by.push(user.to_string());
+        }
+        self.settle_reveal(id, ctx);
+        Ok(())
+    }
+
+    /// Reveals once every voter has accepted, otherwise shares the votes so far.
+    fn settle_reveal(&mut self, id: Uuid, ctx: &mut Context<Self>) {
+        let Some(proposal) = self.reveal_proposals.get(&id) else {
+            return;
+        };
+        let dto = proposal.dto.clone();
+        if dto
+            .voters
+            .iter()
+            .all(|voter| dto.accepted_by.contains(voter))
+        {
+            self.resolve_reveal(id, true, ctx);
+        } else {
+            self.publish(RoomEvent::RevealProposal(dto.clone()));
+            self.broadcast(ServerMessage::RevealProposal(dto));
+        }
+    }
+
+    /// Takes a user who has left out of the votes on reveals proposed here, and withdraws the reveals they proposed.
+    fn reveal_voter_left(&mut self, user: &str, ctx: &mut Context<Self>) {
+        let proposed_here: Vec<Uuid> = self
+            .reveal_proposals
+            .iter()
+            .filter(|(_, proposal)| proposal.proposer.is_some())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in proposed_here {
+            let Some(proposal) = self.reveal_proposals.get_mut(&id) else {
+                continue;
+            };
+            if proposal.dto.proposed_by == user {
+                self.resolve_reveal(id, false, ctx);
+            } else if proposal.dto.voters.iter().any(|voter| voter == user) {
+                proposal.dto.voters.retain(|voter| voter != user);
+                proposal.dto.accepted_by.retain(|voter| voter != user);
+                self.settle_reveal(id, ctx);
+            }
+        }
+    }
+
+    /// Closes a reveal proposed here, revealing the cells if it was accepted.
+    fn resolve_reveal(&mut self, id: Uuid, accepted: bool, ctx: &mut Context<Self>) {
+        let Some(proposal) = self.reveal_proposals.remove(&id) else {
+            return;
+        };
+        self.publish(RoomEvent::RevealResolved { id, accepted });
+        self.broadcast(ServerMessage::RevealResolved { id, accepted });
+        if let (true, Some(proposer)) = (accepted, proposal.proposer) {
+            self.reveal(
+                proposal.dto.target,
+                &proposal.dto.proposed_by,
+                proposer,
+                ctx,
+            );
+        }
+    }
+
+    /// Writes the solution into every cell of a target that doesn't already hold it, flagged as revealed, and
+    /// logs the reveal.
+    fn reveal(
+        &mut self,
+        target: AssistTarget,
+        user: &str,
+        sender_id: Uuid,
+        ctx: &mut Context<Self>,
+    ) {
+        let letters = match self.reveal_letters(&target) {
+            Ok(letters) => letters,
+            Err(message) => {
+                self.send_to(
+                    &sender_id,
+                    ServerMessage::error(ErrorCode::InvalidMessage, message),
+                );
+                return;
+            }
+        };
+        let items: Vec<SolutionItemDto> = letters
+            .into_iter()
+            .filter(|(position, letter)| {
+                !self
+                    .solution
+                    .get(&(position.x, position.y))
+                    .is_some_and(|item| item.value.eq_ignore_ascii_case(letter))
+            })
+            .map(|(position, letter)| SolutionItemDto {
+                x: position.x,
+                y: position.y,
+                value: letter,
+                state: CellState::Revealed,
+                ..SolutionItemDto::default()
+            })
+            .collect();
+        if !items.is_empty() {
+            self.make_move(user, sender_id, None, items, MoveKind::Reveal, ctx);
+        }
+
+        let (team, crossword) = self.key.clone();
+        log_assist(
+            Data::new(self.pool.clone()),
+            team,
+            crossword,
+            user.to_string(),
+            "reveal",
+            target,
+        )
+        .into_actor(self)
+        .map(|result, act, _| {
+            if let Err(e) = result {
+                println!("Error logging reveal for {:?}: {}", act.key, e);
+            }
+        })
+        .spawn(ctx);
+    }
+
+    /// Records the solve as finished and tells everyone, the first time the grid is full and correct.
+    fn detect_completion(&mut self, ctx: &mut Context<Self>) {
+        if self.completion.is_some() || self.recording_completion {
+            return;
+        }
+        let Some(crossword) = &self.crossword else {
+            return;
+        };
+        if !is_complete(crossword, &self.solution) {
+            return;
+        }
+        self.recording_completion = true;
+        let final_writers = self
+            .solution
+            .values()
+            .map(|item| item.modified_by.clone())
+            .filter(|user| !user.is_empty())
+            .collect();
+        let (team, crossword) = self.key.clone();
+        record_completion(Data::new(self.pool.clone()), team, crossword, final_writers)
+            .into_actor(self)
+            .map(|result, act, _| {
+                act.recording_completion = false;
+                match result {
+                    Ok(completion) => {
+                        act.completion = Some(completion.clone());
+                        act.broadcast(ServerMessage::Solved(completion));
+                        act.set_timer(act.timer.clone());
+                    }
+                    Err(e) => println!("Error recording completion for {:?}: {}", act.key, e),
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Changes the timer, starting or stopping it to match the room, and shares it if anything changed. The timer
+    /// runs once started while anyone is in the room, unless it is paused or the crossword has been finished.
+    fn set_timer(&mut self, timer: TimerDto) {
+        let running = timer.started_at.is_some()
+            && !timer.paused
+            && self.completion.is_none()
+            && !self.roster().is_empty();
+        let timer = if running == timer.running {
+            timer
+        } else {
+            with_running(&timer, running, Utc::now().timestamp_millis())
+        };
+        if timer == self.timer {
+            return;
+        }
+        self.timer = timer.clone();
+        self.publish(RoomEvent::Timer(timer.clone()));
+        self.broadcast(ServerMessage::Timer(timer.clone()));
+
+        // The writer saves timers in order, and a room that starts after this one closes loads after the last one
+        self.writer.do_send(QueueTimer {
+            room: self.key.clone(),
+            timer,
+        });
+    }
+
+    /// Loads the room's solution and state from the database, after anything still queued has been persisted. When
+    /// `reloading`, the solution is replaced, such as after moves failed to persist, and everyone is sent a snapshot.
+    fn load(&mut self, reloading: bool, ctx: &mut Context<Self>) {
+        // Nothing else in this room is handled until its solution has loaded, leaving other rooms unaffected
+        let predecessor = self.predecessor.take();
+        let writer = self.writer.clone();
+        let pool = Data::new(self.pool.clone());
+        let key = self.key.clone();
+        let (team, crossword) = key.clone();
+        async move {
+            if let Some(predecessor) = predecessor {
+                // Fails straight away once the previous room has stopped
+                let _ = predecessor.send(Close).await;
+            }
+            // Persist anything still queued so the loaded solution includes it
+            writer
+                .send(Flush { room: key })
+                .await
+                .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+            let seq = get_solution_seq(pool.clone(), crossword.clone(), team.clone()).await?;
+            let items =
+                retrieve_and_send_solution(pool.clone(), team.clone(), crossword.clone()).await?;
+            let chat_history =
+                get_chat_history(pool.clone(), team.clone(), crossword.clone(), CHAT_HISTORY)
+                    .await?;
+            let completion =
+                retrieve_completion(pool.clone(), team.clone(), crossword.clone()).await?;
+            let timer = retrieve_timer(pool.clone(), team.clone(), crossword.clone()).await?;
+            let crossword = match get_crossword_by_id(pool, crossword).await {
+                Ok(crossword) => Some(crossword),
+                Err(AppError::CrosswordNotFound(_)) => None,
+                Err(e) => return Err(e),
+            };
+            Ok::<_, AppError>((seq, items, chat_history, completion, timer, crossword))
+        }
+        .into_actor(self)
+        .map(move |result, act, _| match result {
+            Ok((seq, items, chat_history, completion, timer, crossword)) => {
+                act.chat_history = chat_history.into();
+                act.completion = completion;
+                act.timer = timer;
+                // Cells stored before moves were validated may not be on the grid
+                let white_cells = crossword.as_ref().map(white_cells);
+                act.crossword = crossword;
+                act.solution = items
+                    .into_iter()
+                    .filter(|item| {
+                        white_cells
+                            .as_ref()
+                            .is_none_or(|white_cells| white_cells.contains(&(item.x, item.y)))
+                    })
+                    .map(|item| ((item.x, item.y), item))
+                    .collect();
+                act.load_error = None;
+                if reloading {
+                    // Clients may have seen moves that are now gone, so only a snapshot can bring them up to date
+                    act.log = RoomLog {
+                        seq: act.log.seq.max(seq) + 1,
+                        ..RoomLog::default()
+                    };
+                    act.broadcast(act.snapshot());
+                } else {
+                    act.log.seq = seq;
+                    act.publish(RoomEvent::PresenceRequest);
+                }
+            }
+            Err(e) => {
+                println!("Error loading room {:?}: {}", act.key, e);
+                act.load_error = Some(e);
+            }
+        })
+        .wait(ctx);
+    }
+
+    fn snapshot(&self) -> ServerMessage {
+        ServerMessage::Snapshot {
+            seq: self.log.seq,
+            items: self.solution.values().cloned().collect(),
+            completion: self.completion.clone(),
+            timer: self.timer.clone(),
+        }
+    }
+}
+
+impl Actor for Room {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.load(false, ctx);
+        ctx.run_interval(IDLE_CHECK_INTERVAL, |act, _| act.mark_idle_cursors());
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| act.heartbeat(ctx));
+    }
+}
+
+impl Handler<Join> for Room {
+    type Result = MessageResult<Join>;
+
+    fn handle(&mut self, msg: Join, _: &mut Context<Self>) -> Self::Result {
+        let user = msg.session.user.clone();
+        let was_present = self.presence(&user).is_some();
+        let was_present_locally = self.local_presence(&user).is_some();
+        self.members.insert(msg.session.id, msg.clone());
+
+        // Other tabs of a user already present don't change who is solving
+        if let Some(presence) = self.local_presence(&user) {
+            if !was_present_locally {
+                self.publish(RoomEvent::Join(presence));
+            }
+        }
+        if let Some(presence) = self.presence(&user) {
+            if !was_present {
+                self.broadcast_to_others(&msg.session, ServerMessage::Join(presence));
+            }
+        }
+        self.set_timer(self.timer.clone());
+        self.send_to(
+            &msg.session.id,
+            ServerMessage::Roster {
+                members: self.roster(),
+            },
+        );
+
+        self.send_to(
+            &msg.session.id,
+            ServerMessage::ChatHistory {
+                messages: self.chat_history.iter().cloned().collect(),
+            },
+        );
+
+        for proposal in self.reveal_proposals.values() {
+            self.send_to(
+                &msg.session.id,
+                ServerMessage::RevealProposal(proposal.dto.clone()),
+            );
+        }
+
+        // Broadcast all current positions to the new client
+        self.broadcast_all_current_positions(&msg.session);
+
+        if let Some(e) = &self.load_error {
+            return MessageResult(ServerMessage::error(
+                ErrorCode::InternalError,
+                e.to_string(),
+            ));
+        }
+
+        // A reconnecting client only needs the moves it missed, if we still have them all
+        if let Some(moves) = msg
+            .session
+            .last_seq
+            .and_then(|last_seq| self.log.moves_since(last_seq))
+        {
+            return MessageResult(ServerMessage::Resume {
+                seq: self.log.seq,
+                moves,
+                completion: self.completion.clone(),
+                timer: self.timer.clone(),
+            });
+        }
+        MessageResult(self.snapshot())
+    }
+}
+
+impl Handler<Leave> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: Leave, ctx: &mut Context<Self>) {
+        if let Some(member) = self.members.remove(&msg.id) {
+            let user = member.session.user;
+            if self.local_presence(&user).is_none() {
+                self.publish(RoomEvent::Leave { user: user.clone() });
+                self.user_left(&user, ctx);
+                self.set_timer(self.timer.clone());
+            }
+        }
+    }
+}
+
+impl Handler<Close> for Room {
+    type Result = ();
+
+    fn handle(&mut self, _: Close, ctx: &mut Context<Self>) {
+        ctx.stop();
+    }
+}
+
+impl Handler<RemoveMember> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: RemoveMember, _: &mut Context<Self>) {
+        // Each session leaves the room as usual once it has stopped
+        for member in self.members.values() {
+            if member.session.user == msg.user {
+                member.addr.do_send(ws_session::Close {
+                    reason: "You are no longer a member of this team".to_string(),
+                });
+            }
+        }
+    }
+}
+
+impl Handler<Move> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: Move, ctx: &mut Context<Self>) {
+        let validated = match &self.crossword {
+            Some(crossword) => validate_solution_items(crossword, msg.solution_items),
+            None => Err(format!("Could not find crossword for id: {}", self.key.1)),
+        };
+        let solution_items = match validated {
+            Ok(solution_items) => solution_items,
+            Err(message) => {
+                self.send_to(
+                    &msg.sender.id,
+                    ServerMessage::error(ErrorCode::InvalidMessage, message),
+                );
+                return;
+            }
+        };
+        self.make_move(
+            &msg.sender.user,
+            msg.sender.id,
+            msg.client_move_id,
+            solution_items,
+            MoveKind::Move,
+            ctx,
+        );
+    }
+}
+
+impl Handler<Undo> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: Undo, ctx: &mut Context<Self>) {
+        self.undo_or_redo(msg.sender, msg.client_move_id, false, ctx);
+    }
+}
+
+impl Handler<Redo> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: Redo, ctx: &mut Context<Self>) {
+        self.undo_or_redo(msg.sender, msg.client_move_id, true, ctx);
+    }
+}
+
+impl Handler<CurrentCell> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: CurrentCell, _: &mut Context<Self>) {
+        let clue = match self.clue_at(msg.x, msg.y, msg.direction.as_ref(), msg.clue) {
+            Ok(clue) => clue,
+            Err(message) => {
+                self.send_to(
+                    &msg.sender.id,
+                    ServerMessage::error(ErrorCode::InvalidMessage, message),
+                );
+                return;
+            }
+        };
+        let current_cell = CurrentCellDto {
+            x: msg.x,
+            y: msg.y,
+            user: msg.sender.user.clone(),
+            idle: false,
+            direction: msg.direction,
+            clue,
+        };
+
+        // Update the session's current cell
+        if let Some(member) = self.members.get_mut(&msg.sender.id) {
+            member.session.current_cell = Some(current_cell.clone());
+        }
+        self.move_cursor(current_cell.clone());
+
+        // Broadcast the current cell update to everyone in the room
+        self.broadcast_current_cell(current_cell);
+    }
+}
+
+impl Handler<Chat> for Room {
+    type Result = ();
+
+    /// Stores a chat message and then shares it with everyone in the room, including the sender.
+    fn handle(&mut self, msg: Chat, ctx: &mut Context<Self>) {
+        let text = match validate_chat_text(&msg.text) {
+            Ok(text) => text,
+            Err(message) => {
+                self.send_to(
+                    &msg.sender.id,
+                    ServerMessage::error(ErrorCode::InvalidMessage, message),
+                );
+                return;
+            }
+        };
+        let (team, crossword) = self.key.clone();
+        let sender_id = msg.sender.id;
+        send_chat_message(
+            Data::new(self.pool.clone()),
+            team,
+            crossword,
+            msg.sender.user,
+            msg.sender.name,
+            text,
+        )
+        .into_actor(self)
+        .map(move |result, act, _| match result {
+            Ok(chat_message) => {
+                act.record_chat(&chat_message);
+                act.publish(RoomEvent::Chat(chat_message.clone()));
+                act.broadcast(ServerMessage::Chat(chat_message));
+            }
+            Err(e) => {
+                println!("Error storing chat message for {:?}: {}", act.key, e);
+                act.send_to(
+                    &sender_id,
+                    ServerMessage::error(ErrorCode::InternalError, e.to_string()),
+                );
+            }
+        })
+        .spawn(ctx);
+    }
+}
+
+impl Handler<Check> for Room {
+    type Result = ();
+
+    /// Checks the room's solution against the crossword's, telling only the session that asked.
+    fn handle(&mut self, msg: Check, ctx: &mut Context<Self>) {
+        let result = self
+            .published_crossword()
+            .and_then(|crossword| check(crossword, &self.solution, &msg.target));
+        let result = match result {
+            Ok(result) => result,
+            Err(message) => {
+                self.send_to(
+                    &msg.sender.id,
+                    ServerMessage::error(ErrorCode::InvalidMessage, message),
+                );
+                return;
+            }
+        };
+        self.send_to(
+            &msg.sender.id,
+            ServerMessage::CheckResult {
+                target: msg.target.clone(),
+                correct: result.correct,
+                incorrect: result.incorrect,
+            },
+        );
+
+        let (team, crossword) = self.key.clone();
+        log_assist(
+            Data::new(self.pool.clone()),
+            team,
+            crossword,
+            msg.sender.user,
+            "check",
+            msg.target,
+        )
+        .into_actor(self)
+        .map(|result, act, _| {
+            if let Err(e) = result {
+                println!("Error logging check for {:?}: {}", act.key, e);
+            }
+        })
+        .spawn(ctx);
+    }
+}
+
+impl Handler<Reveal> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: Reveal, ctx: &mut Context<Self>) {
+        if let Err(message) = self.reveal_letters(&msg.target) {
+            self.send_to(
+                &msg.sender.id,
+                ServerMessage::error(ErrorCode::InvalidMessage, message),
+            );
+            return;
+        }
+        retrieve_team_settings(Data::new(self.pool.clone()), self.key.0.clone())
+            .into_actor(self)
+            .map(move |result, act, ctx| match result {
+                Ok(settings) => {
+                    act.propose_reveal(msg.target, msg.sender, settings.reveal_policy, ctx)
+                }
+                Err(e) => {
+                    println!("Error loading team settings for {:?}: {}", act.key, e);
+                    act.send_to(
+                        &msg.sender.id,
+                        ServerMessage::error(ErrorCode::InternalError, e.to_string()),
+                    );
+                }
+            })
+            .spawn(ctx);
+    }
+}
+
+impl Handler<RevealVote> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: RevealVote, ctx: &mut Context<Self>) {
+        let user = msg.sender.user;
+        let result = match self.reveal_proposals.get(&msg.id) {
+            None => Err("No such reveal proposal".to_string()),
+            Some(proposal) if proposal.proposer.is_some() => {
+                self.count_reveal_vote(msg.id, &user, msg.accept, ctx)
+            }
+            // The room on the instance it was proposed on counts the votes
+            Some(proposal) if proposal.dto.voters.contains(&user) => {
+                self.publish(RoomEvent::RevealVote {
+                    id: msg.id,
+                    user,
+                    accept: msg.accept,
+                });
+                Ok(())
+            }
+            Some(_) => Err("You aren't voting on this reveal".to_string()),
+        };
+        if let Err(message) = result {
+            self.send_to(
+                &msg.sender.id,
+                ServerMessage::error(ErrorCode::InvalidMessage, message),
+            );
+        }
+    }
+}
+
+impl Handler<PauseTimer> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: PauseTimer, _: &mut Context<Self>) {
+        self.set_timer(TimerDto {
+            paused: msg.paused,
+            ..self.timer.clone()
+        });
+    }
+}
+
+impl Handler<Comment> for Room {
+    type Result = ();
+
+    /// Stores a comment on a clue and then shares it with everyone in the room, including the sender.
+    fn handle(&mut self, msg: Comment, ctx: &mut Context<Self>) {
+        let validated = validate_comment_text(&msg.text).and_then(|text| match &self.crossword {
+            Some(crossword) if find_entry(crossword, msg.clue, &msg.direction).is_some() => {
+                Ok(text)
+            }
+            Some(_) => Err(format!("No {:?} clue {}", msg.direction, msg.clue)),
+            None => Err(format!("Could not find crossword for id: {}", self.key.1)),
+        });
+        let text = match validated {
+            Ok(text) => text,
+            Err(message) => {
+                self.send_to(
+                    &msg.sender.id,
+                    ServerMessage::error(ErrorCode::InvalidMessage, message),
+                );
+                return;
+            }
+        };
+        let (team, crossword) = self.key.clone();
+        let sender_id = msg.sender.id;
+        add_clue_comment(
+            Data::new(self.pool.clone()),
+            team,
+            crossword,
+            msg.clue,
+            msg.direction,
+            msg.sender.user,
+            msg.sender.name,
+            text,
+        )
+        .into_actor(self)
+        .map(move |result, act, _| match result {
+            Ok(clue_comment) => {
+                act.publish(RoomEvent::Comment(clue_comment.clone()));
+                act.broadcast(ServerMessage::Comment(clue_comment));
+            }
+            Err(e) => {
+                println!("Error storing clue comment for {:?}: {}", act.key, e);
+                act.send_to(
+                    &sender_id,
+                    ServerMessage::error(ErrorCode::InternalError, e.to_string()),
+                );
+            }
+        })
+        .spawn(ctx);
+    }
+}
+
+impl Handler<WriteFailed> for Room {
+    type Result = ();
+
+    fn handle(&mut self, _: WriteFailed, ctx: &mut Context<Self>) {
+        // The sender was told their move was rejected, but everyone has already seen it
+        self.publish(RoomEvent::Reload);
+        self.load(true, ctx);
+    }
+}
+
+impl Handler<RemoteEvent> for Room {
+    type Result = ();
+
+    fn handle(&mut self, msg: RemoteEvent, ctx: &mut Context<Self>) {
+        self.remote_heard_at.insert(msg.instance, Instant::now());
+        match msg.event {
+            RoomEvent::Move(sequenced_move) => {
+                // Already persisted by the instance it was made on
+                let applied_items: Vec<SolutionItemDto> = self
+                    .apply_to_solution(&sequenced_move.items)
+                    .into_iter()
+                    .map(|(_, after)| after)
+                    .collect();
+                if applied_items.is_empty() {
+                    return;
+                }
+                let sequenced_move = SequencedMove {
+                    seq: sequenced_move.seq,
+                    items: applied_items,
+                };
+                self.log.record_remote(sequenced_move.clone());
+                self.broadcast(ServerMessage::Move(sequenced_move));
+                self.detect_completion(ctx);
+            }
+            RoomEvent::Cursor(current_cell) => {
+                self.move_cursor(current_cell.clone());
+                self.broadcast(ServerMessage::Cursor(current_cell));
+            }
+            RoomEvent::Join(presence) => {
+                let user = presence.user.clone();
+                let was_present = self.presence(&user).is_some();
+                if let Some(current_cell) = &presence.current_cell {
+                    if !self.cursors.contains_key(&user) {
+                        self.move_cursor(current_cell.clone());
+                    }
+                }
+                self.remote_presence
+                    .entry(user.clone())
+                    .or_default()
+                    .insert(msg.instance, presence);
+                if let (false, Some(presence)) = (was_present, self.presence(&user)) {
+                    self.broadcast(ServerMessage::Join(presence));
+                }
+            }
+            RoomEvent::Leave { user } => {
+                if let Some(by_instance) = self.remote_presence.get_mut(&user) {
+                    if by_instance.remove(&msg.instance).is_some() {
+                        if by_instance.is_empty() {
+                            self.remote_presence.remove(&user);
+                        }
+                        self.user_left(&user, ctx);
+                    }
+                }
+            }
+            RoomEvent::Chat(chat_message) => {
+                if self.record_chat(&chat_message) {
+                    self.broadcast(ServerMessage::Chat(chat_message));
+                }
+            }
+            RoomEvent::Comment(clue_comment) => {
+                self.broadcast(ServerMessage::Comment(clue_comment));
+            }
+            RoomEvent::RevealProposal(dto) => {
+                self.reveal_proposals.insert(
+                    dto.id,
+                    RevealProposal {
+                        dto: dto.clone(),
+                        proposer: None,
+                    },
+                );
+                self.broadcast(ServerMessage::RevealProposal(dto));
+            }
+            RoomEvent::RevealVote { id, user, accept } => {
+                let proposed_here = self
+                    .reveal_proposals
+                    .get(&id)
+                    .is_some_and(|proposal| proposal.proposer.is_some());
+                if proposed_here {
+                    if let Err(message) = self.count_reveal_vote(id, &user, accept, ctx) {
+                        println!("Ignoring reveal vote from {}: {}", user, message);
+                    }
+                }
+            }
+            RoomEvent::RevealResolved { id, accepted } => {
+                if self.reveal_proposals.remove(&id).is_some() {
+                    self.broadcast(ServerMessage::RevealResolved { id, accepted });
+                }
+            }
+            RoomEvent::Timer(timer) => {
+                self.timer = timer.clone();
+                self.broadcast(ServerMessage::Timer(timer));
+            }
+            RoomEvent::PresenceRequest => {
+                let local_users: BTreeSet<String> = self
+                    .members
+                    .values()
+                    .map(|member| member.session.user.clone())
+                    .collect();
+                for user in local_users {
+                    if let Some(presence) = self.local_presence(&user) {
+                        self.publish(RoomEvent::Join(presence));
+                    }
+                }
+            }
+            RoomEvent::Heartbeat { users } => {
+                self.forget_remote_users(msg.instance, |user| users.iter().any(|u| u == user), ctx);
+            }
+            RoomEvent::Reload => self.load(true, ctx),
+            // Handed to each of the team's rooms as `RemoveMember` by the `MoveServer`
+            RoomEvent::MemberRemoved { .. } => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_with_moves(seqs: impl IntoIterator<Item = i64>) -> RoomLog {
+        let mut log = RoomLog::default();
+        for seq in seqs {
+            log.record(seq, Vec::new());
+        }
+        log
+    }
+
+    fn seqs(moves: Option<Vec<SequencedMove>>) -> Option<Vec<i64>> {
+        moves.map(|moves| {
+            moves
+                .iter()
+                .map(|sequenced_move| sequenced_move.seq)
+                .collect()
+        })
+    }
+
+    #[test]
+    fn resumes_with_the_moves_after_last_seq() {
+        let log = log_with_moves(1..=5);
+        assert_eq!(seqs(log.moves_since(2)), Some(vec![3, 4, 5]));
+        assert_eq!(seqs(log.moves_since(5)), Some(vec![]));
+    }
+
+    #[test]
+    fn resumes_a_room_nobody_has_moved_in() {
+        let log = RoomLog {
+            seq: 7,
+            ..RoomLog::default()
+        };
+        assert_eq!(seqs(log.moves_since(7)), Some(vec![]));
+        assert_eq!(seqs(log.moves_since(6)), None);
+    }
+
+    #[test]
+    fn needs_a_snapshot_for_seqs_the_room_has_not_reached() {
+        let log = log_with_moves(1..=3);
+        assert_eq!(seqs(log.moves_since(4)), None);
+    }
+
+    #[test]
+    fn needs_a_snapshot_once_missed_moves_have_been_dropped() {
+        let log = log_with_moves(1..=(RESUMABLE_MOVES as i64 + 10));
+        assert_eq!(seqs(log.moves_since(5)), None);
+        assert_eq!(
+            seqs(log.moves_since(10)).map(|moves| moves.len()),
+            Some(RESUMABLE_MOVES)
+        );
+    }
+
+    #[test]
+    fn remote_moves_move_the_clock_forward() {
+        let mut log = log_with_moves(1..=2);
+        log.record_remote(SequencedMove {
+            seq: 4,
+            items: Vec::new(),
+        });
+        assert_eq!(log.seq, 4);
+        assert_eq!(seqs(log.moves_since(2)), Some(vec![4]));
+    }
+
+    #[test]
+    fn clients_that_may_have_missed_a_late_move_need_a_snapshot() {
+        let mut log = log_with_moves(1..=5);
+        log.record_remote(SequencedMove {
+            seq: 3,
+            items: Vec::new(),
+        });
+        assert_eq!(log.seq, 5);
+
+        // Seen before the late move's seq, so it is sent along with everything else
+        assert_eq!(seqs(log.moves_since(2)), Some(vec![3, 4, 5, 3]));
+        // Seen past the late move's seq when it arrived, so it can't tell it missed it
+        assert_eq!(seqs(log.moves_since(3)), None);
+        assert_eq!(seqs(log.moves_since(5)), None);
+
+        log.record(6, Vec::new());
+        assert_eq!(seqs(log.moves_since(6)), Some(vec![]));
+    }
+}
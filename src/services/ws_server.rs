@@ -1,138 +1,122 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 
-use crate::models::api_models::{SolutionItemDto, CurrentCellDto};
-use crate::services::solution_service::{retrieve_and_send_solution, update_solution};
-use crate::services::ws_session;
+use crate::models::ws_messages::ServerMessage;
+use crate::services::fanout::{Fanout, Publish, RemoteEvent, RoomEvent};
+use crate::services::solution_writer::SolutionWriter;
+use crate::services::ws_room::{Close, Join, Leave, RemoveMember, Room};
 use crate::services::ws_session::WsSession;
 use crate::DbPool;
 use actix::prelude::*;
-use actix_web::web::Data;
 use uuid::Uuid;
 
 /// New chat session is created
 #[derive(Message, Debug, Clone)]
-#[rtype(String)]
+#[rtype(result = "Result<Joined, MailboxError>")]
 pub struct Connect {
     pub session: WsSession,
     pub addr: Addr<WsSession>,
 }
 
+/// Room a session has joined, along with its initial snapshot or resume frame
+pub struct Joined {
+    pub room: Addr<Room>,
+    pub message: ServerMessage,
+}
+
 /// Session is disconnected
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct Disconnect {
     pub id: Uuid,
+    pub room: RoomKey,
 }
 
+/// User has been removed from a team, so their sessions in its rooms are closed on every instance
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct Move {
-    pub solution_items: Vec<SolutionItemDto>,
-    pub sender: WsSession,
+pub struct MemberRemoved {
+    pub team: String,
+    pub user: String,
 }
 
-#[derive(Message)]
-#[rtype(result = "()")]
-pub struct CurrentCell {
-    pub x: i64,
-    pub y: i64,
-    pub sender: WsSession,
+/// Team and crossword a session is solving.
+pub type RoomKey = (String, String);
+
+/// How long moves from other instances are kept for rooms that start here shortly afterwards,
+/// comfortably longer than the other instance takes to persist them.
+const REMOTE_MOVE_REPLAY_WINDOW: Duration = Duration::from_secs(10);
+
+struct RoomEntry {
+    addr: Addr<Room>,
+    members: HashSet<Uuid>,
 }
 
-#[derive(Clone, Debug)]
+/// Registry of live rooms, creating each on first join and closing it after the last leave.
 pub struct MoveServer {
-    sessions: HashMap<Uuid, Connect>,
+    rooms: HashMap<RoomKey, RoomEntry>,
+    /// Rooms that have been told to close but may not have stopped yet.
+    closing: HashMap<RoomKey, Addr<Room>>,
+    /// Recent moves from other instances, which may not have been persisted yet when a room here loads.
+    recent_remote_moves: VecDeque<(Instant, RemoteEvent)>,
     pool: DbPool,
+    writer: Addr<SolutionWriter>,
+    fanout: Addr<Fanout>,
+    cursor_idle_after: Duration,
 }
 
 impl MoveServer {
-    pub fn new(pool: DbPool) -> MoveServer {
+    pub fn new(
+        pool: DbPool,
+        writer: Addr<SolutionWriter>,
+        fanout: Addr<Fanout>,
+        cursor_idle_after: Duration,
+    ) -> MoveServer {
         MoveServer {
-            sessions: HashMap::new(),
+            rooms: HashMap::new(),
+            closing: HashMap::new(),
+            recent_remote_moves: VecDeque::new(),
             pool,
+            writer,
+            fanout,
+            cursor_idle_after,
         }
     }
-}
 
-impl MoveServer {
-    fn broadcast_moves(&self, sender: WsSession, solution_items: Vec<SolutionItemDto>) {
-        // Add user information to each solution item
-        let solution_items_with_user: Vec<SolutionItemDto> = solution_items
-            .into_iter()
-            .map(|mut item| {
-                item.modified_by = sender.user.clone();
-                item
-            })
-            .collect();
-        
-        for session in self.sessions.clone().into_iter() {
-            let ws_session = session.1.session;
-            if ws_session.crossword == sender.crossword && ws_session.team == sender.team {
-                let result = serde_json::to_string(&solution_items_with_user);
-                match result {
-                    Ok(message) => session.1.addr.do_send(ws_session::Message(message)),
-                    Err(e) => println!("{}", e.to_string()),
-                }
+    fn start_room(&mut self, key: RoomKey) -> Addr<Room> {
+        let predecessor = self.closing.remove(&key);
+        let addr = Room::new(
+            key.clone(),
+            self.pool.clone(),
+            self.writer.clone(),
+            self.fanout.clone(),
+            predecessor,
+            self.cursor_idle_after,
+        )
+        .start();
+
+        // Handled once the room has loaded, so anything not yet persisted elsewhere is still applied
+        for (_, remote_move) in self.recent_remote_moves.iter() {
+            if remote_move.room == key {
+                addr.do_send(remote_move.clone());
             }
         }
+        addr
     }
 
-    fn broadcast_current_cell(&self, sender: WsSession, x: i64, y: i64) {
-        let current_cell = CurrentCellDto {
-            x,
-            y,
-            user: sender.user.clone(),
-        };
-        
-        for session in self.sessions.clone().into_iter() {
-            let ws_session = session.1.session;
-            if ws_session.crossword == sender.crossword && ws_session.team == sender.team {
-                let result = serde_json::to_string(&current_cell);
-                match result {
-                    Ok(message) => session.1.addr.do_send(ws_session::Message(message)),
-                    Err(e) => println!("{}", e.to_string()),
-                }
+    fn remove_member(&self, team: &str, user: &str) {
+        for (key, room) in self.rooms.iter() {
+            if key.0 == team {
+                room.addr.do_send(RemoveMember {
+                    user: user.to_string(),
+                });
             }
         }
     }
+}
 
-    fn broadcast_all_current_positions(&self, new_session: &WsSession) {
-        let mut all_positions: Vec<CurrentCellDto> = Vec::new();
-        
-        // Collect all current positions from other users in the same team and crossword
-        for session in self.sessions.values() {
-            let ws_session = &session.session;
-            if ws_session.crossword == new_session.crossword 
-               && ws_session.team == new_session.team 
-               && ws_session.id != new_session.id {
-                if let Some((x, y)) = ws_session.current_cell {
-                    all_positions.push(CurrentCellDto {
-                        x,
-                        y,
-                        user: ws_session.user.clone(),
-                    });
-                }
-            }
-        }
-        
-        // Send all current positions to the new client
-        println!("all_positions: {:#?}", all_positions);
-        if !all_positions.is_empty() {
-            for position in all_positions {
-
-            let result = serde_json::to_string(&position);
-            match result {
-                Ok(message) => {
-                    if let Some(session) = self.sessions.get(&new_session.id) {
-                        session.addr.do_send(ws_session::Message(message));
-                        println!("broadcasted current positions");
-                    }
-                },
-                Err(e) => println!("{}", e.to_string()),
-            }
-            }
-        }
-    }
+pub fn room_key(session: &WsSession) -> RoomKey {
+    (session.team.clone(), session.crossword.clone())
 }
 
 impl Actor for MoveServer {
@@ -140,24 +124,37 @@ impl Actor for MoveServer {
 }
 
 impl Handler<Connect> for MoveServer {
-    type Result = String;
+    type Result = ResponseFuture<Result<Joined, MailboxError>>;
 
     fn handle(&mut self, msg: Connect, _: &mut Context<Self>) -> Self::Result {
-        println!("Someone joined: {}", msg.session.id.to_string());
-        self.sessions.insert(msg.session.id, msg.clone());
-
-        // Broadcast all current positions to the new client
-        self.broadcast_all_current_positions(&msg.session);
-        
-        let result = futures::executor::block_on(retrieve_and_send_solution(
-            Data::new(self.pool.clone()),
-            msg.session.team.clone(),
-            msg.session.crossword.clone(),
-        ));
-        return match result {
-            Ok(m) => m,
-            Err(e) => e.to_string(),
-        };
+        println!("Someone joined: {}", msg.session.id);
+        let key = room_key(&msg.session);
+        if !self.rooms.contains_key(&key) {
+            let addr = self.start_room(key.clone());
+            self.rooms.insert(
+                key.clone(),
+                RoomEntry {
+                    addr,
+                    members: HashSet::new(),
+                },
+            );
+        }
+        let room = self.rooms.get_mut(&key).expect("room was just started");
+        room.members.insert(msg.session.id);
+
+        let addr = room.addr.clone();
+        Box::pin(async move {
+            let message = addr
+                .send(Join {
+                    session: msg.session,
+                    addr: msg.addr,
+                })
+                .await?;
+            Ok(Joined {
+                room: addr,
+                message,
+            })
+        })
     }
 }
 
@@ -165,43 +162,60 @@ impl Handler<Disconnect> for MoveServer {
     type Result = ();
 
     fn handle(&mut self, msg: Disconnect, _: &mut Context<Self>) {
-        self.sessions.remove(&msg.id);
+        let Some(room) = self.rooms.get_mut(&msg.room) else {
+            return;
+        };
+        if !room.members.remove(&msg.id) {
+            return;
+        }
+        room.addr.do_send(Leave { id: msg.id });
+
+        if room.members.is_empty() {
+            if let Some(room) = self.rooms.remove(&msg.room) {
+                room.addr.do_send(Close);
+                self.closing.retain(|_, addr| addr.connected());
+                self.closing.insert(msg.room, room.addr);
+            }
+        }
     }
 }
 
-impl Handler<Move> for MoveServer {
+impl Handler<MemberRemoved> for MoveServer {
     type Result = ();
 
-    fn handle(&mut self, msg: Move, _: &mut Context<Self>) {
-        let result = futures::executor::block_on(update_solution(
-            Data::new(self.pool.clone()),
-            msg.solution_items.clone(),
-            msg.sender.user.clone(),
-            msg.sender.team.clone(),
-            msg.sender.crossword.clone(),
-        ));
-        match result {
-            Ok(_) => {
-                self.broadcast_moves(msg.sender.clone(), msg.solution_items.clone());
-                println!("broadcasted moves");
-            }
-            Err(e) => {
-                println!("{}", e.to_string())
-            }
-        };
+    fn handle(&mut self, msg: MemberRemoved, _: &mut Context<Self>) {
+        self.remove_member(&msg.team, &msg.user);
+        self.fanout.do_send(Publish {
+            room: (msg.team, String::new()),
+            event: RoomEvent::MemberRemoved { user: msg.user },
+        });
     }
 }
 
-impl Handler<CurrentCell> for MoveServer {
+impl Handler<RemoteEvent> for MoveServer {
     type Result = ();
 
-    fn handle(&mut self, msg: CurrentCell, _: &mut Context<Self>) {
-        // Update the session's current cell
-        if let Some(session) = self.sessions.get_mut(&msg.sender.id) {
-            session.session.current_cell = Some((msg.x, msg.y));
+    fn handle(&mut self, msg: RemoteEvent, _: &mut Context<Self>) {
+        if let RoomEvent::MemberRemoved { user } = &msg.event {
+            self.remove_member(&msg.room.0, user);
+            return;
+        }
+        if let Some(room) = self.rooms.get(&msg.room) {
+            room.addr.do_send(msg.clone());
+        }
+
+        if let RoomEvent::Move(_) = msg.event {
+            let now = Instant::now();
+            while self
+                .recent_remote_moves
+                .front()
+                .is_some_and(|(received, _)| {
+                    now.duration_since(*received) > REMOTE_MOVE_REPLAY_WINDOW
+                })
+            {
+                self.recent_remote_moves.pop_front();
+            }
+            self.recent_remote_moves.push_back((now, msg));
         }
-        
-        // Broadcast the current cell update to all clients in the same team and crossword
-        self.broadcast_current_cell(msg.sender, msg.x, msg.y);
     }
 }
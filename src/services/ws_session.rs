@@ -4,26 +4,42 @@ use std::time::{Duration, Instant};
 use actix::prelude::*;
 use actix_web_actors::ws;
 use actix_web_actors::ws::WebsocketContext;
+use chrono::Utc;
 
-use crate::models::api_models::{SolutionItemDto, CurrentCellDto};
+use crate::models::api_models::CurrentCellDto;
+use crate::models::ws_messages::{parse_client_message, ClientMessage, ServerMessage};
+use crate::services::ws_room::{
+    Chat, Check, Comment, CurrentCell, Move, PauseTimer, Redo, Reveal, RevealVote, Room, Undo,
+};
 use crate::services::ws_server;
-use crate::services::ws_server::{Move, MoveServer};
+use crate::services::ws_server::{room_key, MoveServer};
 use uuid::Uuid;
 
 /// Chat server sends this messages to session
 #[derive(Message)]
 #[rtype(result = "()")]
-pub struct Message(pub String);
+pub struct Message(pub ServerMessage);
+
+/// Closes the session, telling the client why
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct Close {
+    pub reason: String,
+}
 
 #[derive(Clone, Debug)]
 pub struct WsSession {
     pub id: Uuid,
     pub server_addr: Addr<MoveServer>,
+    pub room_addr: Option<Addr<Room>>,
     pub hb: Instant,
     pub user: String,
+    pub name: String,
+    pub connected_at: i64,
     pub team: String,
     pub crossword: String,
-    pub current_cell: Option<(i64, i64)>,
+    pub current_cell: Option<CurrentCellDto>,
+    pub last_seq: Option<i64>,
 }
 
 impl Actor for WsSession {
@@ -39,10 +55,11 @@ impl Actor for WsSession {
                 addr,
             })
             .into_actor(self)
-            .then(|res, _, ctx| {
+            .then(|res, act, ctx| {
                 match res {
-                    Ok(m) => {
-                        ctx.text(m);
+                    Ok(Ok(joined)) => {
+                        act.room_addr = Some(joined.room);
+                        act.send(joined.message, ctx);
                     }
                     _ => ctx.stop(),
                 }
@@ -53,8 +70,10 @@ impl Actor for WsSession {
 
     fn stopping(&mut self, _: &mut Self::Context) -> Running {
         // notify chat server
-        self.server_addr
-            .do_send(ws_server::Disconnect { id: self.id });
+        self.server_addr.do_send(ws_server::Disconnect {
+            id: self.id,
+            room: room_key(self),
+        });
         Running::Stop
     }
 }
@@ -77,30 +96,66 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
                 ctx.stop();
             }
             Ok(ws::Message::Nop) => {}
-            Ok(ws::Message::Text(s)) => {
-                // Try to parse as current cell message first
-                let current_cell_result: Result<CurrentCellDto, _> = serde_json::from_str(s.borrow());
-                match current_cell_result {
-                    Ok(current_cell) => {
-                        self.server_addr.do_send(ws_server::CurrentCell {
-                            x: current_cell.x,
-                            y: current_cell.y,
-                            sender: self.clone(),
-                        });
-                    }
-                    Err(_) => {
-                        // If not a current cell message, try to parse as move message
-                        let value: Result<Vec<SolutionItemDto>, _> = serde_json::from_str(s.borrow());
-                        match value {
-                            Ok(solution_items) => self.server_addr.do_send(Move {
-                                solution_items,
-                                sender: self.clone(),
-                            }),
-                            Err(e) => println!("Error handling text message: {:#?}", e),
-                        }
-                    }
-                }
-            }
+            Ok(ws::Message::Text(s)) => match parse_client_message(s.borrow()) {
+                Ok(ClientMessage::Move {
+                    client_move_id,
+                    items,
+                }) => self.send_to_room(Move {
+                    client_move_id,
+                    solution_items: items,
+                    sender: self.clone(),
+                }),
+                Ok(ClientMessage::Undo { client_move_id }) => self.send_to_room(Undo {
+                    client_move_id,
+                    sender: self.clone(),
+                }),
+                Ok(ClientMessage::Redo { client_move_id }) => self.send_to_room(Redo {
+                    client_move_id,
+                    sender: self.clone(),
+                }),
+                Ok(ClientMessage::Cursor {
+                    x,
+                    y,
+                    direction,
+                    clue,
+                }) => self.send_to_room(CurrentCell {
+                    x,
+                    y,
+                    direction,
+                    clue,
+                    sender: self.clone(),
+                }),
+                Ok(ClientMessage::Chat { text }) => self.send_to_room(Chat {
+                    text,
+                    sender: self.clone(),
+                }),
+                Ok(ClientMessage::Check { target }) => self.send_to_room(Check {
+                    target,
+                    sender: self.clone(),
+                }),
+                Ok(ClientMessage::Reveal { target }) => self.send_to_room(Reveal {
+                    target,
+                    sender: self.clone(),
+                }),
+                Ok(ClientMessage::RevealVote { id, accept }) => self.send_to_room(RevealVote {
+                    id,
+                    accept,
+                    sender: self.clone(),
+                }),
+                Ok(ClientMessage::PauseTimer) => self.send_to_room(PauseTimer { paused: true }),
+                Ok(ClientMessage::ResumeTimer) => self.send_to_room(PauseTimer { paused: false }),
+                Ok(ClientMessage::Comment {
+                    clue,
+                    direction,
+                    text,
+                }) => self.send_to_room(Comment {
+                    clue,
+                    direction,
+                    text,
+                    sender: self.clone(),
+                }),
+                Err(e) => self.send(e.into(), ctx),
+            },
             Err(e) => println!("Error handling stream: {:#?}", e),
         }
     }
@@ -110,20 +165,61 @@ impl Handler<Message> for WsSession {
     type Result = ();
 
     fn handle(&mut self, msg: Message, ctx: &mut Self::Context) {
-        ctx.text(msg.0);
+        self.send(msg.0, ctx);
+    }
+}
+
+impl Handler<Close> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: Close, ctx: &mut Self::Context) {
+        ctx.close(Some(ws::CloseReason {
+            code: ws::CloseCode::Policy,
+            description: Some(msg.reason),
+        }));
+        ctx.stop();
     }
 }
 
 impl WsSession {
-    pub fn new(addr: Addr<MoveServer>, user: String, team: String, crossword: String) -> WsSession {
+    pub fn new(
+        addr: Addr<MoveServer>,
+        user: String,
+        name: String,
+        team: String,
+        crossword: String,
+        last_seq: Option<i64>,
+    ) -> WsSession {
         WsSession {
             id: Uuid::new_v4(),
             server_addr: addr,
+            room_addr: None,
             hb: Instant::now(),
             user,
+            name,
+            connected_at: Utc::now().timestamp_millis(),
             team,
             crossword,
             current_cell: None,
+            last_seq,
+        }
+    }
+
+    fn send_to_room<M>(&self, message: M)
+    where
+        M: actix::Message + Send + 'static,
+        M::Result: Send,
+        Room: Handler<M>,
+    {
+        if let Some(room_addr) = &self.room_addr {
+            room_addr.do_send(message);
+        }
+    }
+
+    fn send(&self, message: ServerMessage, ctx: &mut WebsocketContext<WsSession>) {
+        match message.to_frame() {
+            Ok(frame) => ctx.text(frame),
+            Err(e) => println!("Error serializing message: {:#?}", e),
         }
     }
 
@@ -131,8 +227,10 @@ impl WsSession {
         ctx.run_interval(Duration::new(1, 0), |act, ctx| {
             // check client heartbeats
             if Instant::now().duration_since(act.hb) > Duration::new(10, 0) {
-                act.server_addr
-                    .do_send(ws_server::Disconnect { id: act.id });
+                act.server_addr.do_send(ws_server::Disconnect {
+                    id: act.id,
+                    room: room_key(act),
+                });
                 ctx.stop();
             }
 